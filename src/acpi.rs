@@ -0,0 +1,159 @@
+use core::{mem, ptr};
+
+use x86_64::PhysAddr;
+
+use crate::memory;
+
+// Errors that may occur while locating an ACPI table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcpiError {
+    RsdpNotFound,
+    InvalidChecksum([u8; 4]),
+    TableNotFound([u8; 4]),
+}
+
+// The Root System Description Pointer, left in low memory by the firmware.
+// Fields after `rsdt_address` are only valid for revision 2 (ACPI 2.0+).
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct Rsdp {
+    signature: [u8; 8],
+    checksum: u8,
+    oem_id: [u8; 6],
+    revision: u8,
+    rsdt_address: u32,
+    length: u32,
+    xsdt_address: u64,
+    extended_checksum: u8,
+    _reserved: [u8; 3],
+}
+
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+
+// Size of the ACPI 1.0 portion of the RSDP, covered by `checksum`.
+const RSDP_V1_LENGTH: usize = 20;
+
+// Common header shared by every System Description Table.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct SdtHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+// Describes the location of a register block (e.g., the HPET's registers).
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct GenericAddress {
+    pub address_space_id: u8,
+    pub register_bit_width: u8,
+    pub register_bit_offset: u8,
+    pub access_size: u8,
+    pub address: u64,
+}
+
+// Address space ID for registers that live in system memory (i.e., MMIO).
+pub const ADDRESS_SPACE_SYSTEM_MEMORY: u8 = 0;
+
+// Reads a (possibly unaligned) value of type `T` from physical memory.
+//
+// Unsafe because the caller must ensure that `addr` refers to a valid `T`.
+unsafe fn read_physical<T: Copy>(addr: PhysAddr) -> T {
+    let virtual_addr = memory::physical_to_virtual(addr);
+
+    ptr::read_unaligned(virtual_addr.as_ptr::<T>())
+}
+
+// Verifies that the `length` bytes starting at `addr` sum to zero (mod 256).
+unsafe fn checksum_is_valid(addr: PhysAddr, length: usize) -> bool {
+    let virtual_addr = memory::physical_to_virtual(addr);
+
+    let bytes = core::slice::from_raw_parts(virtual_addr.as_ptr::<u8>(), length);
+
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+}
+
+// Searches for the RSDP on a 16-byte boundary inside the given physical range.
+unsafe fn search_for_rsdp(start: u64, end: u64) -> Option<PhysAddr> {
+    (start..end).step_by(16).map(PhysAddr::new).find(|&addr| {
+        read_physical::<[u8; 8]>(addr) == *RSDP_SIGNATURE
+            && checksum_is_valid(addr, RSDP_V1_LENGTH)
+    })
+}
+
+fn find_rsdp() -> Result<Rsdp, AcpiError> {
+    unsafe {
+        // The RSDP is either in the first KiB of the Extended BIOS Data Area
+        // (whose segment is stored at 0x40e), or in the BIOS ROM area.
+        let ebda_segment = read_physical::<u16>(PhysAddr::new(0x40e));
+        let ebda_start = (ebda_segment as u64) << 4;
+
+        let rsdp_addr = search_for_rsdp(ebda_start, ebda_start + 1024)
+            .or_else(|| search_for_rsdp(0xe0000, 0x100000))
+            .ok_or(AcpiError::RsdpNotFound)?;
+
+        Ok(read_physical::<Rsdp>(rsdp_addr))
+    }
+}
+
+// Returns the physical address of the first ACPI table with the given
+// signature (e.g., `b"HPET"`), checking the table's checksum.
+pub fn find_table(signature: &[u8; 4]) -> Result<PhysAddr, AcpiError> {
+    let rsdp = find_rsdp()?;
+
+    // Prefers the XSDT (64-bit entries) when the firmware provides one.
+    let (root_addr, entry_size) = if rsdp.revision >= 2 && rsdp.xsdt_address != 0 {
+        (PhysAddr::new(rsdp.xsdt_address), mem::size_of::<u64>())
+    } else {
+        (PhysAddr::new(rsdp.rsdt_address as u64), mem::size_of::<u32>())
+    };
+
+    unsafe {
+        let root: SdtHeader = read_physical(root_addr);
+
+        if !checksum_is_valid(root_addr, root.length as usize) {
+            return Err(AcpiError::InvalidChecksum(root.signature));
+        }
+
+        let header_size = mem::size_of::<SdtHeader>();
+        let entry_count = (root.length as usize - header_size) / entry_size;
+
+        for index in 0..entry_count {
+            let entry_addr = root_addr + (header_size + index * entry_size) as u64;
+
+            let table_addr = PhysAddr::new(match entry_size {
+                8 => read_physical::<u64>(entry_addr),
+                _ => read_physical::<u32>(entry_addr) as u64,
+            });
+
+            let table: SdtHeader = read_physical(table_addr);
+
+            if table.signature == *signature {
+                if !checksum_is_valid(table_addr, table.length as usize) {
+                    return Err(AcpiError::InvalidChecksum(table.signature));
+                }
+
+                return Ok(table_addr);
+            }
+        }
+    }
+
+    Err(AcpiError::TableNotFound(*signature))
+}
+
+// Reads the ACPI table of type `T` (which must begin with an `SdtHeader`) with
+// the given signature.
+//
+// Unsafe because the caller must ensure that `T` matches the table's layout.
+pub unsafe fn read_table<T: Copy>(signature: &[u8; 4]) -> Result<T, AcpiError> {
+    let table_addr = find_table(signature)?;
+
+    Ok(read_physical::<T>(table_addr))
+}
@@ -1,4 +1,10 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
 use x86_64::{
+    instructions::interrupts,
     structures::paging::{
         mapper::MapToError, FrameAllocator, Mapper, Page, PageTableFlags, Size4KiB,
     },
@@ -40,7 +46,8 @@ pub(self) fn align_up(address: usize, align: usize) -> usize {
 }
 
 pub const HEAP_START: usize = 0x_4444_4444_0000;
-pub const HEAP_SIZE: usize = 100 * 1024; // 100 KiB
+// Room for thousands of tasks at once (see the executor's stress test).
+pub const HEAP_SIZE: usize = 1024 * 1024; // 1 MiB
 
 // pub struct Dummy;
 // unsafe impl GlobalAlloc for Dummy {
@@ -54,6 +61,65 @@ pub const HEAP_SIZE: usize = 100 * 1024; // 100 KiB
 // #[global_allocator]
 // static ALLOCATOR: Dummy = Dummy;
 
+// Heap usage counters, updated by the global allocator.
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static DEALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES_IN_USE: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES_IN_USE: AtomicUsize = AtomicUsize::new(0);
+
+fn record_alloc(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+
+    let in_use = BYTES_IN_USE.fetch_add(size, Ordering::Relaxed) + size;
+
+    PEAK_BYTES_IN_USE.fetch_max(in_use, Ordering::Relaxed);
+}
+
+fn record_dealloc(size: usize) {
+    DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+
+    BYTES_IN_USE.fetch_sub(size, Ordering::Relaxed);
+}
+
+// A point-in-time copy of the heap usage counters. Sizes are as requested by
+// callers, so they don't include block rounding or allocator overhead.
+#[derive(Debug, Clone, Copy)]
+pub struct HeapStats {
+    pub allocations: usize,
+    pub deallocations: usize,
+    pub bytes_in_use: usize,
+    pub peak_bytes_in_use: usize,
+}
+
+pub fn stats() -> HeapStats {
+    // The allocator updates the counters with interrupts disabled, so they
+    // can't change while we read them here.
+    interrupts::without_interrupts(|| HeapStats {
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+        bytes_in_use: BYTES_IN_USE.load(Ordering::Relaxed),
+        peak_bytes_in_use: PEAK_BYTES_IN_USE.load(Ordering::Relaxed),
+    })
+}
+
+impl fmt::Display for HeapStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "heap: {} of {} bytes in use (peak {})",
+            self.bytes_in_use, HEAP_SIZE, self.peak_bytes_in_use
+        )?;
+
+        write!(
+            f,
+            "allocations: {}, deallocations: {}, live: {}",
+            self.allocations,
+            self.deallocations,
+            self.allocations.saturating_sub(self.deallocations)
+        )
+    }
+}
+
 #[global_allocator]
 // static ALLOCATOR: Locked<BumpAllocator> = Locked::new(BumpAllocator::new());
 // static ALLOCATOR: Locked<LinkedListAllocator> = Locked::new(LinkedListAllocator::new());
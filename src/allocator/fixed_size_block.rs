@@ -4,6 +4,8 @@ use core::mem;
 
 use alloc::alloc::{GlobalAlloc, Layout};
 
+use x86_64::instructions::interrupts;
+
 use super::{linked_list::LinkedListAllocator, Locked};
 
 struct ListNode {
@@ -53,11 +55,23 @@ impl FixedSizeBlockAllocator {
     }
 }
 
+// Allocation and deallocation run with interrupts disabled, so that a thread
+// can't be preempted while holding the allocator's lock.
 unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        interrupts::without_interrupts(|| self.allocate(layout))
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        interrupts::without_interrupts(|| self.deallocate(ptr, layout))
+    }
+}
+
+impl Locked<FixedSizeBlockAllocator> {
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8 {
         let mut allocator = self.lock();
 
-        match FixedSizeBlockAllocator::get_list_index(&layout) {
+        let ptr = match FixedSizeBlockAllocator::get_list_index(&layout) {
             Some(index) => {
                 // This allocation will fit inside one of our fixed block sizes.
 
@@ -83,12 +97,20 @@ unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
                 }
             }
             None => allocator.fallback_alloc(layout),
+        };
+
+        if !ptr.is_null() {
+            super::record_alloc(layout.size());
         }
+
+        ptr
     }
 
-    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
         let mut allocator = self.lock();
 
+        super::record_dealloc(layout.size());
+
         match FixedSizeBlockAllocator::get_list_index(&layout) {
             Some(index) => {
                 // Creates a new head node on the stack, and links it to the
@@ -5,7 +5,10 @@ use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, Pag
 use pic8259::ChainedPics;
 use spin::Mutex;
 
-use crate::{gdt, print, println};
+use crate::{gdt, println};
+
+pub mod deferred;
+pub mod stats;
 
 lazy_static! {
     static ref IDT: InterruptDescriptorTable = {
@@ -29,12 +32,20 @@ lazy_static! {
         // External (system hardware) interrupts
         idt[InterruptIndex::Timer.as_u8()].set_handler_fn(timer_interrupt_handler);
         idt[InterruptIndex::Keyboard.as_u8()].set_handler_fn(keyboard_interrupt_handler);
+        idt[InterruptIndex::Rtc.as_u8()].set_handler_fn(rtc_interrupt_handler);
+        idt[InterruptIndex::Mouse.as_u8()].set_handler_fn(mouse_interrupt_handler);
+        idt[InterruptIndex::PrimarySpurious.as_u8()]
+            .set_handler_fn(primary_spurious_interrupt_handler);
+        idt[InterruptIndex::SecondarySpurious.as_u8()]
+            .set_handler_fn(secondary_spurious_interrupt_handler);
 
         idt
     };
 }
 
 extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
+    stats::record(BREAKPOINT_VECTOR);
+
     println!("EXCEPTION: BREAKPOINT\n{:#?}", stack_frame);
 }
 
@@ -45,6 +56,8 @@ extern "x86-interrupt" fn page_fault_handler(
     use crate::hlt_loop;
     use x86_64::registers::control::Cr2;
 
+    stats::record(PAGE_FAULT_VECTOR);
+
     println!("EXCEPTION: PAGE FAULT");
     println!("Accessed address: {:?}", Cr2::read());
     println!("Error code: {:?}", page_fault_error_code);
@@ -53,18 +66,30 @@ extern "x86-interrupt" fn page_fault_handler(
     hlt_loop();
 }
 
-extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
-    print!(".");
+extern "x86-interrupt" fn timer_interrupt_handler(stack_frame: InterruptStackFrame) {
+    stats::record(InterruptIndex::Timer.as_u8());
+
+    // Driven by either the PIT or the HPET (see `time::set_tick_source()`).
+    crate::time::tick();
+
+    // Reports any executor poll that's been running for too long.
+    crate::task::executor::watchdog::check(&stack_frame);
 
+    // Must come before preemption, since the next thread might not return here
+    // (and so re-enable timer interrupts) for a while.
     unsafe {
         PICS.lock()
             .notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
     }
+
+    crate::thread::on_timer_tick(crate::time::ticks());
 }
 
 extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
     use x86_64::instructions::port::Port;
 
+    stats::record(InterruptIndex::Keyboard.as_u8());
+
     let mut ps2_data_port = Port::new(0x60);
 
     // Reads a byte from the PS2 interface.
@@ -80,30 +105,183 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStac
     }
 }
 
+extern "x86-interrupt" fn rtc_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    stats::record(InterruptIndex::Rtc.as_u8());
+
+    crate::time::rtc::handle_interrupt();
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Rtc.as_u8());
+    }
+}
+
+extern "x86-interrupt" fn mouse_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    use x86_64::instructions::port::Port;
+
+    stats::record(InterruptIndex::Mouse.as_u8());
+
+    let mut ps2_data_port = Port::new(0x60);
+
+    // Reads one byte of a movement packet from the PS2 interface.
+    let byte: u8 = unsafe { ps2_data_port.read() };
+
+    crate::task::mouse::add_byte(byte);
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Mouse.as_u8());
+    }
+}
+
+// IRQ7 and IRQ15 are raised spuriously when an IRQ is de-asserted before the
+// PIC delivers it; in that case, the PIC's in-service register won't have the
+// line's bit set, and we must not acknowledge the (non-existent) interrupt.
+extern "x86-interrupt" fn primary_spurious_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    stats::record(InterruptIndex::PrimarySpurious.as_u8());
+
+    if read_in_service_registers()[0] & (1 << 7) == 0 {
+        stats::record_spurious(InterruptIndex::PrimarySpurious);
+
+        return;
+    }
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::PrimarySpurious.as_u8());
+    }
+}
+
+extern "x86-interrupt" fn secondary_spurious_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    stats::record(InterruptIndex::SecondarySpurious.as_u8());
+
+    if read_in_service_registers()[1] & (1 << 7) == 0 {
+        stats::record_spurious(InterruptIndex::SecondarySpurious);
+
+        // The primary PIC did see a (real) interrupt on its cascade line, so
+        // it alone still expects an EOI.
+        unsafe {
+            x86_64::instructions::port::Port::<u8>::new(PIC_1_COMMAND_PORT).write(PIC_EOI);
+        }
+
+        return;
+    }
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::SecondarySpurious.as_u8());
+    }
+}
+
 extern "x86-interrupt" fn double_fault_handler(
     stack_frame: InterruptStackFrame,
     _error_code: u64,
 ) -> ! {
+    stats::record(DOUBLE_FAULT_VECTOR);
+
     panic!("EXCEPTION: DOUBLE FAULT\n{:#?}", stack_frame);
 }
 
+// Vectors of the CPU exceptions we handle.
+pub const BREAKPOINT_VECTOR: u8 = 3;
+pub const DOUBLE_FAULT_VECTOR: u8 = 8;
+pub const PAGE_FAULT_VECTOR: u8 = 14;
+
 pub const PIC_1_OFFSET: u8 = 32;
 pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 
 pub static PICS: Mutex<ChainedPics> =
     Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
 
+const PIC_1_COMMAND_PORT: u16 = 0x20;
+const PIC_2_COMMAND_PORT: u16 = 0xa0;
+
+const PIC_EOI: u8 = 0x20;
+
+// OCW3 command that makes the next read of a PIC's command port return its
+// in-service register (ISR).
+const PIC_READ_ISR: u8 = 0x0b;
+
+// Reads the in-service registers of the primary and secondary PICs.
+fn read_in_service_registers() -> [u8; 2] {
+    use x86_64::instructions::port::Port;
+
+    let mut primary: Port<u8> = Port::new(PIC_1_COMMAND_PORT);
+    let mut secondary: Port<u8> = Port::new(PIC_2_COMMAND_PORT);
+
+    unsafe {
+        primary.write(PIC_READ_ISR);
+        secondary.write(PIC_READ_ISR);
+
+        [primary.read(), secondary.read()]
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 #[repr(u8)]
 pub enum InterruptIndex {
     Timer = PIC_1_OFFSET,
     Keyboard,
+    PrimarySpurious = PIC_1_OFFSET + 7,
+    Rtc = PIC_2_OFFSET,
+    Mouse = PIC_2_OFFSET + 4,
+    SecondarySpurious = PIC_2_OFFSET + 7,
 }
 
 impl InterruptIndex {
     fn as_u8(self) -> u8 {
         self as u8
     }
+
+    fn from_u8(vector: u8) -> Option<Self> {
+        [
+            InterruptIndex::Timer,
+            InterruptIndex::Keyboard,
+            InterruptIndex::PrimarySpurious,
+            InterruptIndex::Rtc,
+            InterruptIndex::Mouse,
+            InterruptIndex::SecondarySpurious,
+        ]
+        .into_iter()
+        .find(|index| index.as_u8() == vector)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            InterruptIndex::Timer => "timer",
+            InterruptIndex::Keyboard => "keyboard",
+            InterruptIndex::PrimarySpurious => "IRQ7 (spurious?)",
+            InterruptIndex::Rtc => "rtc",
+            InterruptIndex::Mouse => "mouse",
+            InterruptIndex::SecondarySpurious => "IRQ15 (spurious?)",
+        }
+    }
+
+    // The interrupt's IRQ line (0-15), across both PICs.
+    fn as_irq(self) -> u8 {
+        self.as_u8() - PIC_1_OFFSET
+    }
+}
+
+// Clears the PIC mask bit for the given interrupt's IRQ line (and for the
+// cascade line, IRQ2, if the interrupt is routed through the secondary PIC).
+pub fn unmask_irq(index: InterruptIndex) {
+    let irq = index.as_irq();
+
+    x86_64::instructions::interrupts::without_interrupts(|| unsafe {
+        let mut pics = PICS.lock();
+
+        let [mut primary_mask, mut secondary_mask] = pics.read_masks();
+
+        if irq < 8 {
+            primary_mask &= !(1 << irq);
+        } else {
+            primary_mask &= !(1 << 2);
+            secondary_mask &= !(1 << (irq - 8));
+        }
+
+        pics.write_masks(primary_mask, secondary_mask);
+    });
 }
 
 pub fn init_idt() {
@@ -116,3 +294,12 @@ fn test_breakpoint_exception() {
 
     x86_64::instructions::interrupts::int3();
 }
+
+#[test_case]
+fn test_breakpoint_is_counted() {
+    let before = stats::count(3);
+
+    x86_64::instructions::interrupts::int3();
+
+    assert_eq!(stats::count(3), before + 1);
+}
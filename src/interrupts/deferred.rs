@@ -0,0 +1,135 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+};
+
+use conquer_once::spin::OnceCell;
+
+use crossbeam_queue::ArrayQueue;
+
+use futures_util::task::AtomicWaker;
+
+// Interrupt handlers do only what must happen in interrupt context (reading a
+// device's data port, counting timer ticks, preempting threads, waking tasks),
+// and defer anything else, e.g. printing, through this queue. The queue is
+// drained by `process_deferred_work_task()`, a high-priority executor task,
+// rather than on interrupt exit: what's left in the handlers is too little to
+// be worth moving.
+
+// Maximum number of work items that may be pending at once.
+const QUEUE_CAPACITY: usize = 128;
+
+// A small unit of work, pushed by an interrupt handler to be run later from
+// task context (with interrupts enabled, and with no locks held). It holds no
+// heap data, so scheduling it never allocates.
+#[derive(Clone, Copy)]
+pub struct Work {
+    func: fn(u64),
+    arg: u64,
+}
+
+impl Work {
+    pub const fn new(func: fn(u64), arg: u64) -> Self {
+        Work { func, arg }
+    }
+
+    fn run(self) {
+        (self.func)(self.arg)
+    }
+}
+
+// Like SCANCODE_QUEUE, the queue is allocated once (from task context) by
+// `init()`; afterwards, handlers can push to it without allocating.
+static QUEUE: OnceCell<ArrayQueue<Work>> = OnceCell::uninit();
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+// Work items that couldn't be queued (queue full or uninitialized).
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+// Allocates the work queue; must be called after the heap is initialized.
+pub fn init() {
+    QUEUE
+        .try_init_once(|| ArrayQueue::new(QUEUE_CAPACITY))
+        .expect("deferred::init() should only be called once!");
+}
+
+// Queues `work` to run in task context. Safe to call from interrupt handlers;
+// returns false (and counts the work as dropped) if the queue is full.
+pub fn schedule(work: Work) -> bool {
+    let queued = match QUEUE.try_get() {
+        Ok(queue) => queue.push(work).is_ok(),
+        Err(_) => false,
+    };
+
+    if queued {
+        WAKER.wake();
+    } else {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    queued
+}
+
+pub fn dropped_count() -> u64 {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+// Runs every pending work item, returning how many were run. Must only be
+// called from task context, with interrupts enabled.
+pub fn run_pending() -> usize {
+    let queue = match QUEUE.try_get() {
+        Ok(queue) => queue,
+        Err(_) => return 0,
+    };
+
+    let mut count = 0;
+
+    while let Ok(work) = queue.pop() {
+        work.run();
+
+        count += 1;
+    }
+
+    count
+}
+
+// Resolves once there is at least one pending work item.
+struct PendingWork;
+
+impl Future for PendingWork {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let queue = QUEUE
+            .try_get()
+            .expect("deferred work queue uninitialized!");
+
+        if !queue.is_empty() {
+            return Poll::Ready(());
+        }
+
+        // As with ScancodeStream, we re-check after registering, in case an
+        // interrupt handler pushed work in between.
+        WAKER.register(cx.waker());
+
+        if queue.is_empty() {
+            Poll::Pending
+        } else {
+            WAKER.take();
+
+            Poll::Ready(())
+        }
+    }
+}
+
+// Executor task that drains the deferred work queue, forever.
+pub async fn process_deferred_work_task() {
+    loop {
+        PendingWork.await;
+
+        run_pending();
+    }
+}
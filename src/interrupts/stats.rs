@@ -0,0 +1,101 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use super::{InterruptIndex, BREAKPOINT_VECTOR, DOUBLE_FAULT_VECTOR, PAGE_FAULT_VECTOR};
+
+// Number of times each IDT vector has fired since boot. These are global for
+// now; once we bring up other CPUs, each CPU should get its own set.
+static COUNTS: [AtomicU64; 256] = [const { AtomicU64::new(0) }; 256];
+
+// Spurious IRQs (i.e., IRQ7 or IRQ15 with no corresponding in-service bit)
+// from the primary and secondary PICs, respectively.
+static SPURIOUS_PRIMARY: AtomicU64 = AtomicU64::new(0);
+static SPURIOUS_SECONDARY: AtomicU64 = AtomicU64::new(0);
+
+// Called by every interrupt handler, with the handler's vector.
+pub(crate) fn record(vector: u8) {
+    COUNTS[vector as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_spurious(index: InterruptIndex) {
+    match index {
+        InterruptIndex::SecondarySpurious => &SPURIOUS_SECONDARY,
+        _ => &SPURIOUS_PRIMARY,
+    }
+    .fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn count(vector: u8) -> u64 {
+    COUNTS[vector as usize].load(Ordering::Relaxed)
+}
+
+pub fn spurious_counts() -> (u64, u64) {
+    (
+        SPURIOUS_PRIMARY.load(Ordering::Relaxed),
+        SPURIOUS_SECONDARY.load(Ordering::Relaxed),
+    )
+}
+
+// A point-in-time copy of every counter, for diagnostics.
+pub struct Snapshot {
+    counts: [u64; 256],
+    spurious_primary: u64,
+    spurious_secondary: u64,
+}
+
+pub fn snapshot() -> Snapshot {
+    let mut counts = [0; 256];
+
+    for (vector, count) in counts.iter_mut().enumerate() {
+        *count = COUNTS[vector].load(Ordering::Relaxed);
+    }
+
+    let (spurious_primary, spurious_secondary) = spurious_counts();
+
+    Snapshot {
+        counts,
+        spurious_primary,
+        spurious_secondary,
+    }
+}
+
+impl Snapshot {
+    // Iterates over (vector, count) pairs for every vector that has fired.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(vector, &count)| (vector as u8, count))
+    }
+}
+
+fn vector_name(vector: u8) -> &'static str {
+    match vector {
+        BREAKPOINT_VECTOR => "breakpoint",
+        DOUBLE_FAULT_VECTOR => "double fault",
+        PAGE_FAULT_VECTOR => "page fault",
+        _ => match InterruptIndex::from_u8(vector) {
+            Some(index) => index.name(),
+            None => "",
+        },
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "VECTOR  NAME                COUNT")?;
+
+        for (vector, count) in self.iter() {
+            writeln!(f, "{:>6}  {:<18}  {}", vector, vector_name(vector), count)?;
+        }
+
+        write!(
+            f,
+            "spurious: {} (primary PIC), {} (secondary PIC)",
+            self.spurious_primary, self.spurious_secondary
+        )
+    }
+}
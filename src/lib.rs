@@ -11,12 +11,16 @@ use core::panic::PanicInfo;
 #[cfg(test)]
 use bootloader::{entry_point, BootInfo};
 
+pub mod acpi;
 pub mod allocator;
 pub mod gdt;
 pub mod interrupts;
 pub mod memory;
+pub mod ps2;
 pub mod serial;
 pub mod task;
+pub mod thread;
+pub mod time;
 pub mod vga_buffer;
 
 pub trait Testable {
@@ -30,9 +34,11 @@ where
     fn run(&self) -> () {
         serial_print!("{}...\t", core::any::type_name::<T>());
 
+        let start = time::Instant::now();
+
         self();
 
-        serial_println!("[ok]");
+        serial_println!("[ok] ({:?})", start.elapsed());
     }
 }
 
@@ -40,6 +46,10 @@ where
 pub fn test_runner(tests: &[&dyn Testable]) {
     serial_println!("Running {} tests:", tests.len());
 
+    // Some integration tests never call `init()`; calibrating here (a no-op if
+    // it's already happened) lets us report per-test timings regardless.
+    time::init();
+
     for test in tests {
         test.run();
     }
@@ -58,9 +68,21 @@ pub fn hlt_loop() -> ! {
 entry_point!(kernel_main);
 
 #[cfg(test)]
-fn kernel_main(_boot_info: &'static BootInfo) -> ! {
+fn kernel_main(boot_info: &'static BootInfo) -> ! {
+    use memory::BootInfoFrameAllocator;
+    use x86_64::VirtAddr;
+
     init();
 
+    // Some unit tests allocate (e.g., the line editor's).
+    let physical_offset = VirtAddr::new(boot_info.physical_memory_offset);
+
+    let mut mapper = unsafe { memory::init(physical_offset) };
+
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("Heap initialization failed.");
+
     test_main();
 
     hlt_loop();
@@ -102,6 +124,22 @@ pub fn exit_qemu(exit_code: QemuExitCode) {
 pub fn init() {
     gdt::init();
     interrupts::init_idt();
+    time::init();
+
+    // Without a working controller, the keyboard may still work in whatever
+    // mode the firmware left it in.
+    if let Err(error) = ps2::init(ps2::ScancodeMode::Translated) {
+        log!("WARNING: PS/2 controller initialization failed: {:?}", error);
+    }
+
     unsafe { interrupts::PICS.lock().initialize() }
+
+    // Not every machine has a mouse (or a controller with a port for one).
+    if ps2::is_dual_channel() {
+        if let Err(error) = ps2::init_mouse() {
+            println!("WARNING: PS/2 mouse initialization failed: {:?}", error);
+        }
+    }
+
     x86_64::instructions::interrupts::enable();
 }
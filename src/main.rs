@@ -11,8 +11,19 @@ use core::panic::PanicInfo;
 use bootloader::{entry_point, BootInfo};
 
 use rust_os::{
-    allocator, println,
-    task::{executor::Executor, keyboard::print_keypresses_task, Task},
+    allocator, interrupts, log, print, println,
+    ps2::{self, TypematicDelay},
+    task::{
+        executor::Executor,
+        keyboard::{
+            hotkey, keyboard_task,
+            layout::{self, Layout},
+            LineReader,
+        },
+        Priority, Task,
+    },
+    thread,
+    time::{self, TickSource},
 };
 use x86_64::structures::paging::Page;
 
@@ -26,6 +37,8 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
 
     rust_os::init();
 
+    println!("Boot time: {}", time::rtc::now());
+
     let physical_offset = VirtAddr::new(boot_info.physical_memory_offset);
 
     let mut mapper = unsafe { memory::init(physical_offset) };
@@ -46,20 +59,56 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
 
     allocator::init_heap(&mut mapper, &mut frame_allocator).expect("Heap initialization failed.");
 
+    // Prefers the HPET over the legacy PIT as our timer tick source.
+    match time::hpet::init(&mut mapper, &mut frame_allocator)
+        .and_then(|_| time::set_tick_source(TickSource::Hpet))
+    {
+        Ok(()) => println!("Using the HPET as the timer tick source."),
+        Err(error) => println!("HPET unavailable ({:?}); using the PIT.", error),
+    }
+
+    // Starts preemptive threading; the executor below runs as the boot thread.
+    thread::init();
+
+    // Allocates the queue through which interrupt handlers defer their work.
+    interrupts::deferred::init();
+
     // Initializes our task executor.
     let mut executor = Executor::new();
 
+    // Runs work deferred by interrupt handlers.
+    executor.spawn(Task::with_priority(
+        interrupts::deferred::process_deferred_work_task(),
+        Priority::InterruptDeferred,
+    ));
+
     // Moves a Future to the heap and pins it.
     let example_task_pinned = Task::new(example_task());
 
     // Enqueues an example task in the executor's work queue.
     executor.spawn(example_task_pinned);
 
+    // The boot-time keyboard layout. There's no boot configuration to read it
+    // from, so switching at runtime (Ctrl+Alt+L cycles through the layouts)
+    // is the only way to change it.
+    layout::set_layout(Layout::Us104);
+
+    // Alt+SysRq+H lists the diagnostic hotkeys.
+    hotkey::register_defaults();
+
+    // Held keys start repeating after 500ms, at about 11 characters a second.
+    if let Err(error) = ps2::set_typematic(0x0c, TypematicDelay::Ms500) {
+        log!("WARNING: couldn't set the keyboard repeat rate: {:?}", error);
+    }
+
+    // Decodes keyboard input for every `KeyEventStream`.
+    executor.spawn(Task::with_priority(keyboard_task(), Priority::Interactive));
+
     // Moves a Future to the heap and pins it.
-    let print_keypresses_task_pinned = Task::new(print_keypresses_task());
+    let echo_lines_task_pinned = Task::with_priority(echo_lines_task(), Priority::Interactive);
 
-    // Enqueues a print-keypresses task.
-    executor.spawn(print_keypresses_task_pinned);
+    // Enqueues a task that reads (and echoes) lines of keyboard input.
+    executor.spawn(echo_lines_task_pinned);
 
     // Polls tasks until all tasks are complete.
     executor.run();
@@ -82,6 +131,19 @@ async fn example_task() {
     println!("async number is {}", number);
 }
 
+// Reads lines of keyboard input, with line editing, and echoes them back.
+async fn echo_lines_task() {
+    let mut reader = LineReader::new();
+
+    loop {
+        print!("> ");
+
+        let line = reader.read_line().await;
+
+        println!("You typed: {}", line);
+    }
+}
+
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
@@ -1,10 +1,15 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
 use x86_64::{
     structures::paging::{
-        FrameAllocator, Mapper, OffsetPageTable, Page, PageTable, PhysFrame, Size4KiB,
+        mapper::MapToError, FrameAllocator, Mapper, OffsetPageTable, Page, PageTable,
+        PageTableFlags, PhysFrame, Size4KiB,
     },
     PhysAddr, VirtAddr,
 };
 
+use crate::println;
+
 //  Consists of a list of MemoryRegion structs, which contain the start address,
 //  the length, and the type (unused, reserved, etc.) of each memory region.
 use bootloader::bootinfo::MemoryMap;
@@ -66,12 +71,30 @@ unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
     }
 }
 
+// Virtual address at which the bootloader mapped all of physical memory; set by
+// `init()`, so that drivers can read firmware structures (e.g., ACPI tables).
+static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+// Start of the virtual address range that we hand out for memory-mapped I/O.
+pub const MMIO_START: usize = 0x_5555_5555_0000;
+pub const MMIO_SIZE: usize = 1024 * 1024; // 1 MiB
+
+static MMIO_NEXT: AtomicU64 = AtomicU64::new(MMIO_START as u64);
+
 pub unsafe fn init(physical_offset: VirtAddr) -> OffsetPageTable<'static> {
+    PHYSICAL_MEMORY_OFFSET.store(physical_offset.as_u64(), Ordering::Relaxed);
+
     let level_4_page_table = get_active_level_4_table(physical_offset);
 
     OffsetPageTable::new(level_4_page_table, physical_offset)
 }
 
+// Translates a physical address to a virtual address, through the bootloader's
+// physical memory mapping. Only meaningful after `init()` has been called.
+pub fn physical_to_virtual(addr: PhysAddr) -> VirtAddr {
+    VirtAddr::new(PHYSICAL_MEMORY_OFFSET.load(Ordering::Relaxed) + addr.as_u64())
+}
+
 pub unsafe fn get_active_level_4_table(physical_offset: VirtAddr) -> &'static mut PageTable {
     use x86_64::registers::control::Cr3;
 
@@ -82,6 +105,63 @@ pub unsafe fn get_active_level_4_table(physical_offset: VirtAddr) -> &'static mu
     return get_page_table_at_physical_addr(&physical_addr, &physical_offset);
 }
 
+// Prints the used entries of the active level 4 page table, along with how
+// many entries of each level 3 table beneath them are in use.
+pub fn dump_page_tables() {
+    use x86_64::registers::control::Cr3;
+
+    // Until `init()` has run, we don't know where physical memory is mapped.
+    if PHYSICAL_MEMORY_OFFSET.load(Ordering::Relaxed) == 0 {
+        println!("Page tables unavailable: memory::init() hasn't run yet.");
+
+        return;
+    }
+
+    let (level_4_page_table_frame, _cr3_flags) = Cr3::read();
+
+    let level_4_addr = level_4_page_table_frame.start_address();
+
+    let level_4_table = unsafe { read_page_table(level_4_addr) };
+
+    println!("L4 table at {:#x}:", level_4_addr.as_u64());
+
+    for (index, entry) in level_4_table.iter().enumerate() {
+        if entry.is_unused() {
+            continue;
+        }
+
+        let flags = entry.flags();
+
+        if !flags.contains(PageTableFlags::PRESENT) {
+            println!("{:>5}: {:#014x} (not present)", index, entry.addr().as_u64());
+
+            continue;
+        }
+
+        let level_3_table = unsafe { read_page_table(entry.addr()) };
+
+        let used = level_3_table.iter().filter(|entry| !entry.is_unused()).count();
+
+        println!(
+            "{:>5}: {:#014x} {:?} ({} L3 entries)",
+            index,
+            entry.addr().as_u64(),
+            flags,
+            used
+        );
+    }
+}
+
+// Returns a shared reference to the page table at `addr`, through the physical
+// memory mapping. Unlike `get_active_level_4_table()`, this never creates a
+// `&mut` to a table that the mapper may also be referencing; the caller must
+// only read through it, and must not hold it across page table changes.
+unsafe fn read_page_table(addr: PhysAddr) -> &'static PageTable {
+    let table: *const PageTable = physical_to_virtual(addr).as_ptr();
+
+    &*table
+}
+
 pub unsafe fn get_page_table_at_physical_addr(
     addr: &PhysAddr,
     physical_offset: &VirtAddr,
@@ -92,6 +172,84 @@ pub unsafe fn get_page_table_at_physical_addr(
 
     &mut *page_table // unsafe
 }
+
+// Maps the 4 KiB physical frame containing `addr` into the MMIO region, as
+// uncached, writable memory, returning the virtual address corresponding to
+// `addr` itself.
+//
+// Unsafe because the caller must guarantee that `addr` really refers to device
+// memory (mapping a frame that's in use elsewhere could alias it).
+pub unsafe fn map_mmio(
+    addr: PhysAddr,
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<VirtAddr, MapToError<Size4KiB>> {
+    let frame = PhysFrame::<Size4KiB>::containing_address(addr);
+
+    // Claims the window's next page; a concurrent caller that got there first
+    // just makes us retry with the page after its own.
+    let mut page_start = MMIO_NEXT.load(Ordering::Relaxed);
+
+    loop {
+        if page_start + 4096 > (MMIO_START + MMIO_SIZE) as u64 {
+            return Err(MapToError::FrameAllocationFailed);
+        }
+
+        match MMIO_NEXT.compare_exchange_weak(
+            page_start,
+            page_start + 4096,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => break,
+            Err(next) => page_start = next,
+        }
+    }
+
+    let page = Page::containing_address(VirtAddr::new(page_start));
+
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_CACHE;
+
+    match mapper.map_to(page, frame, flags, frame_allocator) {
+        Ok(flush) => flush.flush(),
+        Err(error) => {
+            release_mmio_page(page_start);
+
+            return Err(error);
+        }
+    }
+
+    let offset_in_page = addr.as_u64() - frame.start_address().as_u64();
+
+    Ok(page.start_address() + offset_in_page)
+}
+
+// Undoes `map_mmio()` for the page containing `addr`, e.g., when the device
+// turns out to be unusable.
+//
+// Unsafe because the caller must guarantee that nothing still accesses the
+// mapping.
+pub unsafe fn unmap_mmio(addr: VirtAddr, mapper: &mut impl Mapper<Size4KiB>) {
+    let page = Page::<Size4KiB>::containing_address(addr);
+
+    if let Ok((_, flush)) = mapper.unmap(page) {
+        flush.flush();
+    }
+
+    release_mmio_page(page.start_address().as_u64());
+}
+
+// Hands the page at `page_start` back to the MMIO window, if it's still the
+// most recently claimed one; otherwise, it stays unused.
+fn release_mmio_page(page_start: u64) {
+    let _ = MMIO_NEXT.compare_exchange(
+        page_start + 4096,
+        page_start,
+        Ordering::Relaxed,
+        Ordering::Relaxed,
+    );
+}
+
 pub fn create_example_mapping(
     page: Page,
     mapper: &mut OffsetPageTable,
@@ -0,0 +1,615 @@
+use core::{
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+    time::Duration,
+};
+
+use x86_64::instructions::{interrupts, port::Port};
+
+use spin::Mutex;
+
+use crate::{
+    interrupts::deferred::{self, Work},
+    time::Instant,
+};
+
+// Driver for the 8042 PS/2 controller, the keyboard on its first port, and
+// the mouse on its second.
+// The controller is a shared, stateful device, and while we're talking to it,
+// its replies mustn't be mistaken for input by the keyboard and mouse
+// interrupt handlers. So we only talk to it with interrupts disabled, or with
+// both devices' interrupts disabled at the controller (see
+// `without_device_interrupts()`).
+
+const DATA_PORT: u16 = 0x60;
+// Reads return the status register; writes send controller commands.
+const COMMAND_PORT: u16 = 0x64;
+
+// Status register bits.
+const STATUS_OUTPUT_FULL: u8 = 1 << 0;
+const STATUS_INPUT_FULL: u8 = 1 << 1;
+// Set when the waiting output byte came from the second (mouse) port.
+const STATUS_PORT_2_DATA: u8 = 1 << 5;
+
+// Controller commands.
+const READ_CONFIG: u8 = 0x20;
+const WRITE_CONFIG: u8 = 0x60;
+const DISABLE_PORT_2: u8 = 0xa7;
+const ENABLE_PORT_2: u8 = 0xa8;
+const TEST_PORT_2: u8 = 0xa9;
+const SELF_TEST: u8 = 0xaa;
+const TEST_PORT_1: u8 = 0xab;
+const DISABLE_PORT_1: u8 = 0xad;
+const ENABLE_PORT_1: u8 = 0xae;
+// Sends the next byte written to the data port to the second port's device.
+const WRITE_PORT_2: u8 = 0xd4;
+// Pulses the CPU's reset line.
+const RESET_CPU: u8 = 0xfe;
+
+// Configuration byte bits.
+const CONFIG_PORT_1_INTERRUPT: u8 = 1 << 0;
+const CONFIG_PORT_2_INTERRUPT: u8 = 1 << 1;
+const CONFIG_PORT_2_CLOCK_DISABLED: u8 = 1 << 5;
+const CONFIG_TRANSLATION: u8 = 1 << 6;
+
+const SELF_TEST_PASSED: u8 = 0x55;
+const PORT_TEST_PASSED: u8 = 0x00;
+
+// Keyboard (device) commands.
+const SET_LEDS: u8 = 0xed;
+const SCANCODE_SET: u8 = 0xf0;
+const SET_TYPEMATIC: u8 = 0xf3;
+const ENABLE_SCANNING: u8 = 0xf4;
+const DISABLE_SCANNING: u8 = 0xf5;
+const RESET: u8 = 0xff;
+
+// Mouse (device) commands, besides RESET and ENABLE_SCANNING (which also
+// enables a mouse's data reporting).
+const GET_DEVICE_ID: u8 = 0xf2;
+const SET_SAMPLE_RATE: u8 = 0xf3;
+const SET_DEFAULTS: u8 = 0xf6;
+
+// Device IDs returned by a mouse: a standard mouse sends 3-byte packets, and
+// an IntelliMouse (with a scroll wheel) sends 4-byte packets.
+const STANDARD_MOUSE: u8 = 0x00;
+const INTELLIMOUSE: u8 = 0x03;
+
+// Device responses.
+const ACK: u8 = 0xfa;
+const RESEND: u8 = 0xfe;
+const DEVICE_SELF_TEST_PASSED: u8 = 0xaa;
+
+// How many times a command is sent before we give up on it.
+const MAX_ATTEMPTS: usize = 3;
+
+// How long to wait for any one byte from the controller or a device; resets
+// can take much longer than other commands.
+const TIMEOUT: Duration = Duration::from_millis(50);
+const RESET_TIMEOUT: Duration = Duration::from_millis(1000);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ps2Error {
+    // Neither the controller nor a device answered in time.
+    Timeout,
+    // The controller's self-test returned this instead of 0x55.
+    SelfTestFailed(u8),
+    // A port's interface test failed (port number, result code).
+    PortTestFailed(u8, u8),
+    // A device never acknowledged this command (even after resending it).
+    NoAck(u8),
+    // The keyboard's reset self-test returned this instead of 0xAA.
+    DeviceSelfTestFailed(u8),
+    // `init()` hasn't (successfully) run yet.
+    Uninitialized,
+    // The controller has no second port.
+    NoMousePort,
+    // The device on the second port reported this ID, which isn't a mouse's.
+    UnknownDevice(u8),
+}
+
+// How the kernel receives scancodes from the keyboard. Either way, the
+// keyboard itself is switched to scancode set 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ScancodeMode {
+    // The controller translates set 2 into set 1 (the PC/XT-compatible setup
+    // most firmware leaves us with).
+    Translated = 1,
+    // The controller passes set 2 through untouched.
+    Set2 = 2,
+}
+
+// The keyboard's Scroll/Num/Caps Lock LEDs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Leds {
+    pub scroll_lock: bool,
+    pub num_lock: bool,
+    pub caps_lock: bool,
+}
+
+impl Leds {
+    fn as_byte(self) -> u8 {
+        (self.scroll_lock as u8) | (self.num_lock as u8) << 1 | (self.caps_lock as u8) << 2
+    }
+}
+
+// Delay before a held key starts repeating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TypematicDelay {
+    Ms250 = 0,
+    Ms500 = 1,
+    Ms750 = 2,
+    Ms1000 = 3,
+}
+
+// 0 until `init()` succeeds; then the active `ScancodeMode`.
+static MODE: AtomicU8 = AtomicU8::new(0);
+
+// Whether the controller has a second (mouse) port.
+static DUAL_CHANNEL: AtomicBool = AtomicBool::new(false);
+
+// Held while talking to the (initialized) controller; see `with_controller()`.
+static CONTROLLER: Mutex<()> = Mutex::new(());
+
+// 0 until `init_mouse()` succeeds; then the mouse's packet size.
+static MOUSE_PACKET_SIZE: AtomicU8 = AtomicU8::new(0);
+
+// The controller's two ports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Device {
+    Keyboard,
+    Mouse,
+}
+
+struct Controller {
+    data: Port<u8>,
+    command: Port<u8>,
+}
+
+impl Controller {
+    // Unsafe, since the caller must be the only code talking to the
+    // controller (see `with_controller()`), and must keep the interrupt
+    // handler from reading its replies (see the top of this file).
+    unsafe fn new() -> Self {
+        Controller {
+            data: Port::new(DATA_PORT),
+            command: Port::new(COMMAND_PORT),
+        }
+    }
+
+    fn status(&mut self) -> u8 {
+        unsafe { self.command.read() }
+    }
+
+    fn wait_until(
+        &mut self,
+        timeout: Duration,
+        mut ready: impl FnMut(u8) -> bool,
+    ) -> Result<(), Ps2Error> {
+        let start = Instant::now();
+
+        while !ready(self.status()) {
+            if start.elapsed() > timeout {
+                return Err(Ps2Error::Timeout);
+            }
+
+            core::hint::spin_loop();
+        }
+
+        Ok(())
+    }
+
+    fn read(&mut self, timeout: Duration) -> Result<u8, Ps2Error> {
+        self.wait_until(timeout, |status| status & STATUS_OUTPUT_FULL != 0)?;
+
+        Ok(unsafe { self.data.read() })
+    }
+
+    fn write(&mut self, byte: u8) -> Result<(), Ps2Error> {
+        self.wait_until(TIMEOUT, |status| status & STATUS_INPUT_FULL == 0)?;
+
+        unsafe { self.data.write(byte) };
+
+        Ok(())
+    }
+
+    fn send_command(&mut self, command: u8) -> Result<(), Ps2Error> {
+        self.wait_until(TIMEOUT, |status| status & STATUS_INPUT_FULL == 0)?;
+
+        unsafe { self.command.write(command) };
+
+        Ok(())
+    }
+
+    // Discards anything waiting in the output buffer.
+    fn flush(&mut self) {
+        while self.status() & STATUS_OUTPUT_FULL != 0 {
+            unsafe { self.data.read() };
+        }
+    }
+
+    fn config(&mut self) -> Result<u8, Ps2Error> {
+        self.send_command(READ_CONFIG)?;
+
+        self.read(TIMEOUT)
+    }
+
+    fn set_config(&mut self, config: u8) -> Result<(), Ps2Error> {
+        self.send_command(WRITE_CONFIG)?;
+
+        self.write(config)
+    }
+
+    // Sends one byte to a device, resending it until it's acknowledged. Input
+    // that arrives in the meantime is passed on to the keyboard or mouse
+    // service, rather than being lost.
+    fn send_to_device(&mut self, device: Device, byte: u8) -> Result<(), Ps2Error> {
+        for _ in 0..MAX_ATTEMPTS {
+            if device == Device::Mouse {
+                self.send_command(WRITE_PORT_2)?;
+            }
+
+            self.write(byte)?;
+
+            loop {
+                self.wait_until(TIMEOUT, |status| status & STATUS_OUTPUT_FULL != 0)?;
+
+                let from_mouse = self.status() & STATUS_PORT_2_DATA != 0;
+
+                match unsafe { self.data.read() } {
+                    ACK => return Ok(()),
+                    RESEND => break,
+                    input if from_mouse => crate::task::mouse::add_byte(input),
+                    scancode => crate::task::keyboard::add_scancode(scancode),
+                }
+            }
+        }
+
+        Err(Ps2Error::NoAck(byte))
+    }
+
+    // Sends a device command and its argument bytes.
+    fn device_command(
+        &mut self,
+        device: Device,
+        command: u8,
+        args: &[u8],
+    ) -> Result<(), Ps2Error> {
+        self.send_to_device(device, command)?;
+
+        for &arg in args {
+            self.send_to_device(device, arg)?;
+        }
+
+        Ok(())
+    }
+
+    fn keyboard_command(&mut self, command: u8, args: &[u8]) -> Result<(), Ps2Error> {
+        self.device_command(Device::Keyboard, command, args)
+    }
+
+    fn mouse_command(&mut self, command: u8, args: &[u8]) -> Result<(), Ps2Error> {
+        self.device_command(Device::Mouse, command, args)
+    }
+
+    // Runs `f` with both devices' interrupts disabled, so that their replies
+    // are left for us to read, rather than going to the interrupt handlers.
+    // Restores the previous configuration afterwards.
+    fn without_device_interrupts<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, Ps2Error>,
+    ) -> Result<T, Ps2Error> {
+        let config = interrupts::without_interrupts(|| -> Result<u8, Ps2Error> {
+            let config = self.config()?;
+
+            self.set_config(config & !(CONFIG_PORT_1_INTERRUPT | CONFIG_PORT_2_INTERRUPT))?;
+
+            Ok(config)
+        })?;
+
+        let result = f(self);
+
+        self.set_config(config)?;
+
+        result
+    }
+}
+
+// Resets and configures the controller and keyboard, before the keyboard is
+// used.
+pub fn init(mode: ScancodeMode) -> Result<(), Ps2Error> {
+    interrupts::without_interrupts(|| init_controller(&mut unsafe { Controller::new() }, mode))
+}
+
+fn init_controller(controller: &mut Controller, mode: ScancodeMode) -> Result<(), Ps2Error> {
+    // Keeps both devices quiet while we reconfigure the controller.
+    controller.send_command(DISABLE_PORT_1)?;
+    controller.send_command(DISABLE_PORT_2)?;
+
+    controller.flush();
+
+    let mut config = controller.config()?;
+
+    // Interrupts stay off until we're done; translation is set up at the end.
+    config &= !(CONFIG_PORT_1_INTERRUPT | CONFIG_PORT_2_INTERRUPT | CONFIG_TRANSLATION);
+
+    controller.set_config(config)?;
+
+    controller.send_command(SELF_TEST)?;
+
+    match controller.read(TIMEOUT)? {
+        SELF_TEST_PASSED => {}
+        result => return Err(Ps2Error::SelfTestFailed(result)),
+    }
+
+    // The self-test may have reset the controller's configuration.
+    controller.set_config(config)?;
+
+    // A second port exists if enabling it starts its clock.
+    let dual_channel = config & CONFIG_PORT_2_CLOCK_DISABLED != 0 && {
+        controller.send_command(ENABLE_PORT_2)?;
+
+        let enabled = controller.config()? & CONFIG_PORT_2_CLOCK_DISABLED == 0;
+
+        controller.send_command(DISABLE_PORT_2)?;
+
+        enabled
+    };
+
+    controller.send_command(TEST_PORT_1)?;
+
+    match controller.read(TIMEOUT)? {
+        PORT_TEST_PASSED => {}
+        result => return Err(Ps2Error::PortTestFailed(1, result)),
+    }
+
+    if dual_channel {
+        controller.send_command(TEST_PORT_2)?;
+
+        match controller.read(TIMEOUT)? {
+            PORT_TEST_PASSED => {}
+            result => return Err(Ps2Error::PortTestFailed(2, result)),
+        }
+    }
+
+    DUAL_CHANNEL.store(dual_channel, Ordering::Relaxed);
+
+    controller.send_command(ENABLE_PORT_1)?;
+
+    controller.keyboard_command(RESET, &[])?;
+
+    match controller.read(RESET_TIMEOUT)? {
+        DEVICE_SELF_TEST_PASSED => {}
+        result => return Err(Ps2Error::DeviceSelfTestFailed(result)),
+    }
+
+    controller.keyboard_command(DISABLE_SCANNING, &[])?;
+    controller.keyboard_command(SCANCODE_SET, &[2])?;
+    // Num Lock starts out on, as in `KeyModifiers`.
+    let leds = Leds {
+        num_lock: true,
+        ..Leds::default()
+    };
+
+    controller.keyboard_command(SET_LEDS, &[leds.as_byte()])?;
+    controller.keyboard_command(ENABLE_SCANNING, &[])?;
+
+    config |= CONFIG_PORT_1_INTERRUPT;
+
+    if mode == ScancodeMode::Translated {
+        config |= CONFIG_TRANSLATION;
+    }
+
+    controller.set_config(config)?;
+
+    MODE.store(mode as u8, Ordering::Release);
+
+    Ok(())
+}
+
+// The mode chosen at `init()`, or `None` if the controller hasn't been
+// initialized (in which case scancodes arrive in whatever mode the firmware
+// left, which is almost always translated set 1).
+pub fn scancode_mode() -> Option<ScancodeMode> {
+    match MODE.load(Ordering::Acquire) {
+        1 => Some(ScancodeMode::Translated),
+        2 => Some(ScancodeMode::Set2),
+        _ => None,
+    }
+}
+
+// Whether the controller has a second port (for a mouse).
+pub fn is_dual_channel() -> bool {
+    DUAL_CHANNEL.load(Ordering::Relaxed)
+}
+
+// Runs `f` with exclusive access to the (initialized) controller. Interrupts
+// aren't disabled, so `f` must disable them itself, or go through
+// `without_device_interrupts()`. The lock is never taken by an interrupt
+// handler, so a thread that's preempted while holding it can't deadlock us.
+fn with_controller<T>(
+    f: impl FnOnce(&mut Controller) -> Result<T, Ps2Error>,
+) -> Result<T, Ps2Error> {
+    if scancode_mode().is_none() {
+        return Err(Ps2Error::Uninitialized);
+    }
+
+    let _controller = CONTROLLER.lock();
+
+    f(&mut unsafe { Controller::new() })
+}
+
+// Turns the keyboard's lock LEDs on or off.
+pub fn set_leds(leds: Leds) -> Result<(), Ps2Error> {
+    send_leds(leds.as_byte())
+}
+
+// Like `set_leds()`, but returns at once: the LEDs are updated later, as
+// deferred work, so that the caller isn't held up while the keyboard
+// acknowledges the command. Returns false if the update couldn't be queued.
+pub fn set_leds_deferred(leds: Leds) -> bool {
+    deferred::schedule(Work::new(set_leds_work, leds.as_byte() as u64))
+}
+
+fn set_leds_work(byte: u64) {
+    // Only fails if there's no PS/2 keyboard to light up.
+    let _ = send_leds(byte as u8);
+}
+
+fn send_leds(byte: u8) -> Result<(), Ps2Error> {
+    with_controller(|controller| {
+        controller.without_device_interrupts(|controller| {
+            controller.keyboard_command(SET_LEDS, &[byte])
+        })
+    })
+}
+
+// Sets how quickly held keys repeat: `rate` ranges from 0 (30 repeats per
+// second) to 31 (2 per second).
+pub fn set_typematic(rate: u8, delay: TypematicDelay) -> Result<(), Ps2Error> {
+    let byte = typematic_byte(rate, delay);
+
+    with_controller(|controller| {
+        controller.without_device_interrupts(|controller| {
+            controller.keyboard_command(SET_TYPEMATIC, &[byte])
+        })
+    })
+}
+
+// The argument to SET_TYPEMATIC: the delay in bits 5-6, and the rate in bits
+// 0-4.
+fn typematic_byte(rate: u8, delay: TypematicDelay) -> u8 {
+    (delay as u8) << 5 | rate.min(31)
+}
+
+// The size of the mouse's packets: 4 bytes if `init_mouse()` found a scroll
+// wheel, otherwise 3; or `None` if `init_mouse()` hasn't (successfully) run.
+pub fn mouse_packet_size() -> Option<usize> {
+    match MOUSE_PACKET_SIZE.load(Ordering::Acquire) {
+        0 => None,
+        size => Some(size as usize),
+    }
+}
+
+// Resets and enables the mouse on the second port, enabling its scroll wheel
+// if it has one, and unmasks its interrupt (IRQ12). Must be called after the
+// PICs are initialized.
+pub fn init_mouse() -> Result<(), Ps2Error> {
+    if !is_dual_channel() {
+        return Err(Ps2Error::NoMousePort);
+    }
+
+    let packet_size = with_controller(|controller| {
+        let packet_size = controller.without_device_interrupts(|controller| {
+            controller.send_command(ENABLE_PORT_2)?;
+
+            controller.mouse_command(RESET, &[])?;
+
+            match controller.read(RESET_TIMEOUT)? {
+                DEVICE_SELF_TEST_PASSED => {}
+                result => return Err(Ps2Error::DeviceSelfTestFailed(result)),
+            }
+
+            // After its self-test result, the mouse sends its device ID.
+            controller.read(TIMEOUT)?;
+
+            controller.mouse_command(SET_DEFAULTS, &[])?;
+
+            // This "magic" sequence of sample rates turns on an IntelliMouse's
+            // scroll wheel; other mice just ignore it.
+            for rate in [200, 100, 80] {
+                controller.mouse_command(SET_SAMPLE_RATE, &[rate])?;
+            }
+
+            controller.mouse_command(GET_DEVICE_ID, &[])?;
+
+            let packet_size = match controller.read(TIMEOUT)? {
+                INTELLIMOUSE => 4,
+                STANDARD_MOUSE => 3,
+                id => return Err(Ps2Error::UnknownDevice(id)),
+            };
+
+            controller.mouse_command(SET_SAMPLE_RATE, &[100])?;
+            controller.mouse_command(ENABLE_SCANNING, &[])?;
+
+            Ok(packet_size)
+        })?;
+
+        // Only now that the mouse is set up is its interrupt enabled (when
+        // `without_device_interrupts()` restores the configuration, it
+        // restores the old, disabled, setting).
+        interrupts::without_interrupts(|| {
+            let config = controller.config()?;
+
+            let config = (config | CONFIG_PORT_2_INTERRUPT) & !CONFIG_PORT_2_CLOCK_DISABLED;
+
+            controller.set_config(config)
+        })?;
+
+        Ok(packet_size)
+    })?;
+
+    MOUSE_PACKET_SIZE.store(packet_size, Ordering::Release);
+
+    crate::interrupts::unmask_irq(crate::interrupts::InterruptIndex::Mouse);
+
+    Ok(())
+}
+
+// Resets the machine, through the controller's CPU reset line. Should that
+// not work, a triple fault (an exception with no IDT to handle it) will.
+pub fn reboot() -> ! {
+    use x86_64::{instructions::tables::lidt, structures::DescriptorTablePointer, VirtAddr};
+
+    interrupts::disable();
+
+    let _ = unsafe { Controller::new() }.send_command(RESET_CPU);
+
+    // Gives the controller a moment to act.
+    let start = Instant::now();
+
+    while start.elapsed() < TIMEOUT {
+        core::hint::spin_loop();
+    }
+
+    let empty_idt = DescriptorTablePointer {
+        limit: 0,
+        base: VirtAddr::new(0),
+    };
+
+    unsafe {
+        lidt(&empty_idt);
+
+        core::arch::asm!("int3", options(noreturn));
+    }
+}
+
+#[test_case]
+fn test_led_byte() {
+    assert_eq!(Leds::default().as_byte(), 0);
+
+    let leds = Leds {
+        scroll_lock: true,
+        num_lock: false,
+        caps_lock: true,
+    };
+
+    assert_eq!(leds.as_byte(), 0b101);
+
+    let num_lock = Leds {
+        num_lock: true,
+        ..Leds::default()
+    };
+
+    assert_eq!(num_lock.as_byte(), 0b010);
+}
+
+#[test_case]
+fn test_typematic_byte() {
+    assert_eq!(typematic_byte(0, TypematicDelay::Ms250), 0x00);
+    assert_eq!(typematic_byte(0x0c, TypematicDelay::Ms500), 0x2c);
+    assert_eq!(typematic_byte(31, TypematicDelay::Ms1000), 0x7f);
+
+    // Out-of-range rates are clamped to the slowest one.
+    assert_eq!(typematic_byte(200, TypematicDelay::Ms750), 0x5f);
+}
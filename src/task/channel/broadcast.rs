@@ -0,0 +1,253 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+extern crate alloc;
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+
+use spin::Mutex;
+
+use x86_64::instructions::interrupts;
+
+// Errors returned by `Receiver::recv()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    // Every sender has been dropped, and this receiver has seen every value.
+    Closed,
+    // The receiver fell behind, and this many values were overwritten before
+    // it could see them; the next `recv()` returns the oldest retained value.
+    Lagged(u64),
+}
+
+struct State<T> {
+    // The most recent values, oldest first; `buffer[0]` has sequence number
+    // `next_sequence - buffer.len()`.
+    buffer: VecDeque<T>,
+    capacity: usize,
+
+    // Sequence number that the next sent value will get.
+    next_sequence: u64,
+
+    sender_count: usize,
+
+    // Receivers waiting for a new value, by receiver ID. Each receiver has a
+    // single slot, so polling it repeatedly doesn't pile up wakers.
+    wakers: BTreeMap<u64, Waker>,
+    next_receiver_id: u64,
+}
+
+impl<T> State<T> {
+    fn oldest_sequence(&self) -> u64 {
+        self.next_sequence - self.buffer.len() as u64
+    }
+
+    fn new_receiver_id(&mut self) -> u64 {
+        let id = self.next_receiver_id;
+
+        self.next_receiver_id += 1;
+
+        id
+    }
+
+    fn wake_receivers(&mut self) {
+        for (_, waker) in core::mem::take(&mut self.wakers) {
+            waker.wake();
+        }
+    }
+}
+
+// Creates a channel in which every receiver sees every value sent (as long as
+// it keeps up; each receiver may fall at most `capacity` values behind).
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "Broadcast channel capacity must be non-zero.");
+
+    let state = Arc::new(Mutex::new(State {
+        buffer: VecDeque::with_capacity(capacity),
+        capacity,
+        next_sequence: 0,
+        sender_count: 1,
+        wakers: BTreeMap::new(),
+        next_receiver_id: 1,
+    }));
+
+    (
+        Sender {
+            state: state.clone(),
+        },
+        Receiver {
+            state,
+            id: 0,
+            next_sequence: 0,
+        },
+    )
+}
+
+pub struct Sender<T> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T: Clone> Sender<T> {
+    // Sends `value` to every current receiver, overwriting the oldest value if
+    // the channel is full.
+    pub fn send(&self, value: T) {
+        interrupts::without_interrupts(|| {
+            let mut state = self.state.lock();
+
+            if state.buffer.len() == state.capacity {
+                state.buffer.pop_front();
+            }
+
+            state.buffer.push_back(value);
+            state.next_sequence += 1;
+
+            state.wake_receivers();
+        });
+    }
+
+    // Creates a receiver that will see every value sent from now on.
+    pub fn subscribe(&self) -> Receiver<T> {
+        interrupts::without_interrupts(|| {
+            let mut state = self.state.lock();
+
+            Receiver {
+                state: self.state.clone(),
+                id: state.new_receiver_id(),
+                next_sequence: state.next_sequence,
+            }
+        })
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        interrupts::without_interrupts(|| self.state.lock().sender_count += 1);
+
+        Sender {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        interrupts::without_interrupts(|| {
+            let mut state = self.state.lock();
+
+            state.sender_count -= 1;
+
+            if state.sender_count == 0 {
+                state.wake_receivers();
+            }
+        });
+    }
+}
+
+pub struct Receiver<T> {
+    state: Arc<Mutex<State<T>>>,
+
+    // Identifies this receiver's slot in `State::wakers`.
+    id: u64,
+
+    // Sequence number of the next value this receiver will see.
+    next_sequence: u64,
+}
+
+impl<T: Clone> Receiver<T> {
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+
+    // Like `recv()`, for use in hand-written futures and streams.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        interrupts::without_interrupts(|| {
+            let mut state = self.state.lock();
+
+            let oldest_sequence = state.oldest_sequence();
+
+            if self.next_sequence < oldest_sequence {
+                let missed = oldest_sequence - self.next_sequence;
+
+                self.next_sequence = oldest_sequence;
+
+                return Poll::Ready(Err(RecvError::Lagged(missed)));
+            }
+
+            if self.next_sequence < state.next_sequence {
+                let index = (self.next_sequence - oldest_sequence) as usize;
+
+                self.next_sequence += 1;
+
+                return Poll::Ready(Ok(state.buffer[index].clone()));
+            }
+
+            if state.sender_count == 0 {
+                return Poll::Ready(Err(RecvError::Closed));
+            }
+
+            state.wakers.insert(self.id, cx.waker().clone());
+
+            Poll::Pending
+        })
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        Receiver {
+            state: self.state.clone(),
+            id: interrupts::without_interrupts(|| self.state.lock().new_receiver_id()),
+            next_sequence: self.next_sequence,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        interrupts::without_interrupts(|| self.state.lock().wakers.remove(&self.id));
+    }
+}
+
+// Future returned by `Receiver::recv()`.
+pub struct Recv<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T: Clone> Future for Recv<'_, T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+#[test_case]
+fn test_pending_receiver_keeps_one_waker() {
+    use futures_util::task::noop_waker_ref;
+
+    let (sender, mut first) = channel::<u8>(1);
+    let mut second = sender.subscribe();
+
+    let mut cx = Context::from_waker(noop_waker_ref());
+
+    for _ in 0..10 {
+        assert!(first.poll_recv(&mut cx).is_pending());
+        assert!(second.poll_recv(&mut cx).is_pending());
+    }
+
+    assert_eq!(sender.state.lock().wakers.len(), 2);
+
+    drop(second);
+
+    assert_eq!(sender.state.lock().wakers.len(), 1);
+
+    sender.send(1);
+
+    assert!(sender.state.lock().wakers.is_empty());
+    assert_eq!(first.poll_recv(&mut cx), Poll::Ready(Ok(1)));
+}
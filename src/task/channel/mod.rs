@@ -0,0 +1,6 @@
+// Async channels for passing values between kernel tasks (and, for bounded
+// `mpsc` channels, from interrupt handlers to tasks).
+
+pub mod broadcast;
+pub mod mpsc;
+pub mod oneshot;
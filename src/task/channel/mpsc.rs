@@ -0,0 +1,285 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+
+extern crate alloc;
+
+use alloc::sync::Arc;
+
+use crossbeam_queue::{ArrayQueue, SegQueue};
+
+use futures_util::{stream::Stream, task::AtomicWaker};
+
+use crate::task::sync::Notify;
+
+// Error returned when sending to a channel whose receiver has been dropped;
+// carries the unsent value.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+// Error returned by `Sender::try_send()`; carries the unsent value.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    Full(T),
+    Closed(T),
+}
+
+enum Queue<T> {
+    // Allocated once, up front; pushing never allocates, so it's usable from
+    // interrupt handlers.
+    Bounded(ArrayQueue<T>),
+    // Grows as needed; pushing may allocate.
+    Unbounded(SegQueue<T>),
+}
+
+impl<T> Queue<T> {
+    fn push(&self, value: T) -> Result<(), T> {
+        match self {
+            Queue::Bounded(queue) => queue.push(value).map_err(|error| error.0),
+            Queue::Unbounded(queue) => {
+                queue.push(value);
+
+                Ok(())
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<T> {
+        match self {
+            Queue::Bounded(queue) => queue.pop().ok(),
+            Queue::Unbounded(queue) => queue.pop().ok(),
+        }
+    }
+}
+
+struct Chan<T> {
+    queue: Queue<T>,
+
+    // The receiving task's waker.
+    receiver_waker: AtomicWaker,
+
+    // Notified whenever the receiver frees a slot (bounded channels only), or
+    // goes away.
+    space_available: Notify,
+
+    sender_count: AtomicUsize,
+    receiver_closed: AtomicBool,
+}
+
+impl<T> Chan<T> {
+    fn new(queue: Queue<T>) -> Arc<Self> {
+        Arc::new(Chan {
+            queue,
+            receiver_waker: AtomicWaker::new(),
+            space_available: Notify::new(),
+            sender_count: AtomicUsize::new(1),
+            receiver_closed: AtomicBool::new(false),
+        })
+    }
+
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        if self.receiver_closed.load(Ordering::Acquire) {
+            return Err(TrySendError::Closed(value));
+        }
+
+        self.queue.push(value).map_err(TrySendError::Full)?;
+
+        self.receiver_waker.wake();
+
+        Ok(())
+    }
+}
+
+// Creates a channel that holds at most `capacity` values. Its `try_send()`
+// never allocates, so interrupt handlers may use it.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "Channel capacity must be non-zero.");
+
+    let chan = Chan::new(Queue::Bounded(ArrayQueue::new(capacity)));
+
+    (
+        Sender { chan: chan.clone() },
+        Receiver { chan },
+    )
+}
+
+// Creates a channel with no upper bound on its length. Sending may allocate,
+// so it mustn't be done from interrupt handlers.
+pub fn unbounded_channel<T>() -> (UnboundedSender<T>, Receiver<T>) {
+    let chan = Chan::new(Queue::Unbounded(SegQueue::new()));
+
+    (
+        UnboundedSender { chan: chan.clone() },
+        Receiver { chan },
+    )
+}
+
+pub struct Sender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> Sender<T> {
+    // Sends `value`, waiting for space if the channel is full.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut value = value;
+
+        loop {
+            match self.chan.try_send(value) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Closed(unsent)) => return Err(SendError(unsent)),
+                Err(TrySendError::Full(unsent)) => value = unsent,
+            }
+
+            // If the receiver frees a slot before we start waiting, `Notify`
+            // keeps a permit for us, so the wakeup isn't lost.
+            self.chan.space_available.notified().await;
+        }
+    }
+
+    // Sends `value` if there's space for it right now. Safe to call from
+    // interrupt handlers.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.chan.try_send(value)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.chan.receiver_closed.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.sender_count.fetch_add(1, Ordering::Relaxed);
+
+        Sender {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // The last sender to leave lets the receiver know that it's done.
+        if self.chan.sender_count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.chan.receiver_waker.wake();
+        }
+    }
+}
+
+pub struct UnboundedSender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> UnboundedSender<T> {
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.chan.try_send(value).map_err(|error| match error {
+            TrySendError::Full(value) | TrySendError::Closed(value) => SendError(value),
+        })
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.chan.receiver_closed.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Clone for UnboundedSender<T> {
+    fn clone(&self) -> Self {
+        self.chan.sender_count.fetch_add(1, Ordering::Relaxed);
+
+        UnboundedSender {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for UnboundedSender<T> {
+    fn drop(&mut self) {
+        if self.chan.sender_count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.chan.receiver_waker.wake();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> Receiver<T> {
+    // Receives the next value, or `None` once every sender has been dropped
+    // (and the channel has been drained).
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+
+    pub fn try_recv(&mut self) -> Option<T> {
+        let value = self.chan.queue.pop()?;
+
+        self.chan.space_available.notify_one();
+
+        Some(value)
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        if let Some(value) = self.try_recv() {
+            // Fast path
+            return Poll::Ready(Some(value));
+        }
+
+        // As with ScancodeStream, registers our waker and then checks again,
+        // in case a value arrived in between.
+        self.chan.receiver_waker.register(cx.waker());
+
+        if let Some(value) = self.try_recv() {
+            self.chan.receiver_waker.take();
+
+            return Poll::Ready(Some(value));
+        }
+
+        if self.chan.sender_count.load(Ordering::Acquire) == 0 {
+            // A final value may have been sent just before the last sender
+            // left.
+            return Poll::Ready(self.try_recv());
+        }
+
+        Poll::Pending
+    }
+
+    // Stops accepting values; values already in the channel can still be
+    // received.
+    pub fn close(&mut self) {
+        self.chan.receiver_closed.store(true, Ordering::Release);
+
+        // Senders waiting for space should give up.
+        self.chan.space_available.notify_waiters();
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+// Future returned by `Receiver::recv()`.
+pub struct Recv<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.receiver.poll_recv(cx)
+    }
+}
@@ -0,0 +1,119 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll},
+};
+
+extern crate alloc;
+
+use alloc::sync::Arc;
+
+use futures_util::task::AtomicWaker;
+
+use spin::Mutex;
+
+use x86_64::instructions::interrupts;
+
+// Error returned when the `Sender` is dropped without sending a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+struct Inner<T> {
+    // Only ever locked with interrupts disabled, like our other spinlocks.
+    value: Mutex<Option<T>>,
+
+    // Set once the sender has either sent a value or been dropped.
+    complete: AtomicBool,
+
+    receiver_closed: AtomicBool,
+    receiver_waker: AtomicWaker,
+}
+
+// Creates a channel for sending a single value, e.g., a reply to a request.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(Inner {
+        value: Mutex::new(None),
+        complete: AtomicBool::new(false),
+        receiver_closed: AtomicBool::new(false),
+        receiver_waker: AtomicWaker::new(),
+    });
+
+    (
+        Sender {
+            inner: inner.clone(),
+        },
+        Receiver { inner },
+    )
+}
+
+pub struct Sender<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Sender<T> {
+    // Sends `value`, handing it back if the receiver has already gone away.
+    //
+    // Sending consumes the sender, and dropping it may free the channel (and
+    // a value that was never received), so this mustn't be called from an
+    // interrupt handler; hand the value to a bounded `mpsc` channel instead.
+    pub fn send(self, value: T) -> Result<(), T> {
+        if self.inner.receiver_closed.load(Ordering::Acquire) {
+            return Err(value);
+        }
+
+        interrupts::without_interrupts(|| *self.inner.value.lock() = Some(value));
+
+        // Dropping `self` marks the channel complete and wakes the receiver.
+        Ok(())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.receiver_closed.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.inner.complete.store(true, Ordering::Release);
+
+        self.inner.receiver_waker.wake();
+    }
+}
+
+// Resolves to the sent value, or to `RecvError` if the sender was dropped.
+pub struct Receiver<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Receiver<T> {
+    // Returns the value if it has already been sent.
+    pub fn try_recv(&mut self) -> Option<T> {
+        interrupts::without_interrupts(|| self.inner.value.lock().take())
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.inner.complete.load(Ordering::Acquire) {
+            return Poll::Ready(self.try_recv().ok_or(RecvError));
+        }
+
+        self.inner.receiver_waker.register(cx.waker());
+
+        // The sender may have completed just before we registered.
+        if self.inner.complete.load(Ordering::Acquire) {
+            return Poll::Ready(self.try_recv().ok_or(RecvError));
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.inner.receiver_closed.store(true, Ordering::Release);
+    }
+}
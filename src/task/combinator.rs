@@ -0,0 +1,204 @@
+use core::{
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+extern crate alloc;
+
+use alloc::{boxed::Box, vec::Vec};
+
+// Combinators for awaiting several futures from within one task. Unlike
+// spawning, these poll every future on the current task; none of them needs
+// to be `Send` or `'static`.
+
+// A future that's either still running, or has finished and is holding its
+// output until it's taken. Used by `join!()`, `select!()` and `join_all()`.
+// The future is boxed, so that it can be polled without pinning its owner.
+#[doc(hidden)]
+pub enum MaybeDone<F: Future> {
+    Running(Pin<Box<F>>),
+    Done(F::Output),
+    Taken,
+}
+
+impl<F: Future> MaybeDone<F> {
+    pub fn new(future: F) -> Self {
+        MaybeDone::Running(Box::pin(future))
+    }
+
+    // Polls the future if it's still running; returns whether it's done.
+    pub fn poll_done(&mut self, cx: &mut Context<'_>) -> bool {
+        match self {
+            MaybeDone::Running(future) => match future.as_mut().poll(cx) {
+                Poll::Ready(output) => {
+                    *self = MaybeDone::Done(output);
+
+                    true
+                }
+                Poll::Pending => false,
+            },
+            MaybeDone::Done(_) => true,
+            MaybeDone::Taken => panic!("MaybeDone polled after its output was taken."),
+        }
+    }
+
+    // Takes the future's output, if it's done.
+    pub fn take_output(&mut self) -> Option<F::Output> {
+        match mem::replace(self, MaybeDone::Taken) {
+            MaybeDone::Done(output) => Some(output),
+            other => {
+                *self = other;
+
+                None
+            }
+        }
+    }
+}
+
+// Waits for every one of the given futures, returning a tuple of their outputs
+// (in the order given). Must be used inside an `async` block or function.
+//
+//     let (a, b) = join!(read_a(), read_b());
+#[macro_export]
+macro_rules! join {
+    // Pairs each future with a `_` for every future before it, so that the
+    // expansion can pick its slot out of the tuple below by pattern matching.
+    (@ { ( $($skip:tt)* ) $($done:tt)* } $future:expr, $($rest:tt)*) => {
+        $crate::join!(@ { ( $($skip)* _ ) $($done)* ( $($skip)* ) $future, } $($rest)*)
+    };
+
+    (@ { ( $($skip:tt)* ) $( ( $($before:tt)* ) $future:expr, )* }) => {{
+        use $crate::task::combinator::MaybeDone;
+
+        let mut futures = ( $( MaybeDone::new($future), )* );
+
+        ::core::future::poll_fn(move |cx| {
+            let mut all_done = true;
+
+            $(
+                let ( $($before,)* future, .. ) = &mut futures;
+
+                all_done &= future.poll_done(cx);
+            )*
+
+            if !all_done {
+                return ::core::task::Poll::Pending;
+            }
+
+            ::core::task::Poll::Ready(( $( {
+                let ( $($before,)* future, .. ) = &mut futures;
+
+                future.take_output().unwrap()
+            }, )* ))
+        })
+        .await
+    }};
+
+    ($($future:expr),+ $(,)?) => {
+        $crate::join!(@ { () } $($future,)+)
+    };
+}
+
+// Waits for the first of several futures to finish, binds its output to the
+// branch's pattern (which must be irrefutable), and evaluates that branch's
+// handler; the other futures are dropped (i.e., cancelled). Branches are
+// polled in order, so earlier branches win ties. Must be used inside an
+// `async` block or function; handlers may `return`, `break`, or use `?`.
+//
+//     select! {
+//         line = reader.read_line() => handle(line),
+//         _ = sleep(timeout) => println!("timed out"),
+//     }
+#[macro_export]
+macro_rules! select {
+    // As in `join!()`, pairs each branch with a `_` for every branch before it.
+    (@ { ( $($skip:tt)* ) $($done:tt)* } $pattern:pat = $future:expr => $handler:expr, $($rest:tt)*) => {
+        $crate::select!(
+            @ { ( $($skip)* _ ) $($done)* ( $($skip)* ) $pattern = $future => $handler, }
+            $($rest)*
+        )
+    };
+
+    (@ { ( $($skip:tt)* ) $( ( $($before:tt)* ) $pattern:pat = $future:expr => $handler:expr, )* }) => {{
+        use $crate::task::combinator::MaybeDone;
+
+        let mut futures = ( $( MaybeDone::new($future), )* );
+
+        ::core::future::poll_fn(|cx| {
+            $(
+                let ( $($before,)* future, .. ) = &mut futures;
+
+                if future.poll_done(cx) {
+                    return ::core::task::Poll::Ready(());
+                }
+            )*
+
+            ::core::task::Poll::Pending
+        })
+        .await;
+
+        // Exactly one future is done: the first one (in order) that finished.
+        $(
+            if let ::core::option::Option::Some(output) = {
+                let ( $($before,)* future, .. ) = &mut futures;
+
+                future.take_output()
+            } {
+                let $pattern = output;
+
+                $handler
+            } else
+        )* {
+            ::core::unreachable!()
+        }
+    }};
+
+    ($($pattern:pat = $future:expr => $handler:expr),+ $(,)?) => {
+        $crate::select!(@ { () } $($pattern = $future => $handler,)+)
+    };
+}
+
+// Waits for every future in `futures`, resolving to their outputs (in order).
+pub fn join_all<I>(futures: I) -> JoinAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    JoinAll {
+        futures: futures.into_iter().map(MaybeDone::new).collect(),
+    }
+}
+
+pub struct JoinAll<F: Future> {
+    futures: Vec<MaybeDone<F>>,
+}
+
+// Every future is boxed (in `MaybeDone`), so none of them is moved by moving
+// the `JoinAll` itself.
+impl<F: Future> Unpin for JoinAll<F> {}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Vec<F::Output>> {
+        let mut all_done = true;
+
+        for future in self.futures.iter_mut() {
+            all_done &= future.poll_done(cx);
+        }
+
+        if !all_done {
+            return Poll::Pending;
+        }
+
+        let outputs = self
+            .futures
+            .iter_mut()
+            .map(|future| future.take_output().unwrap())
+            .collect();
+
+        Poll::Ready(outputs)
+    }
+}
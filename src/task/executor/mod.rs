@@ -1,71 +1,214 @@
 extern crate alloc;
 
-use core::task::{Context, Poll, Waker};
+use core::{
+    future::Future,
+    sync::atomic::Ordering,
+    task::{Context, Poll, Waker},
+};
 
 use alloc::{collections::BTreeMap, sync::Arc, task::Wake};
 
 use x86_64::instructions::interrupts::{self, enable_and_hlt};
 
-use crossbeam_queue::ArrayQueue;
+use conquer_once::spin::OnceCell;
 
-use super::{Task, TaskId};
+use crossbeam_queue::SegQueue;
+
+use crate::time::Instant;
+
+use self::{ready_queue::ReadyQueue, snapshot::TaskRegistry};
+
+use super::{
+    join::{joinable, JoinHandle},
+    Priority, Task, TaskId, TaskStats,
+};
+
+mod ready_queue;
+mod snapshot;
+pub mod watchdog;
+
+pub use self::snapshot::{Snapshot, TaskInfo, TaskState};
+
+// The spawner of the executor that's currently running (if any), so that code
+// without access to the executor (e.g., deferred interrupt work) can spawn.
+static GLOBAL_SPAWNER: OnceCell<Spawner> = OnceCell::uninit();
+
+// Returns a spawner for the running executor, once `Executor::run()` starts.
+pub fn spawner() -> Option<Spawner> {
+    GLOBAL_SPAWNER.try_get().ok().cloned()
+}
+
+// Lists the tasks of the running executor, once `Executor::run()` starts.
+pub fn snapshot() -> Option<Snapshot> {
+    GLOBAL_SPAWNER
+        .try_get()
+        .ok()
+        .map(|spawner| spawner.snapshot())
+}
 
 pub struct Executor {
     // Fast access to tasks via ID lookup.
     tasks: BTreeMap<TaskId, Task>,
 
-    // Reference-counted, so it can be shared between executor and wakers; we
-    // use a fixed-size queue so that interrupt handlers can push to it without
-    // making heap allocations (potential for deadlock).
-    task_queue: Arc<ArrayQueue<TaskId>>,
+    // Reference-counted, so it can be shared between executor and wakers; the
+    // queue never allocates on push, so that interrupt handlers can wake tasks
+    // without making heap allocations (potential for deadlock).
+    task_queue: Arc<ReadyQueue>,
 
     // Fast access to wakers via ID lookup; lets us re-use any existing waker
     // instance for subsequent wake-ups of a given task; also, holding one
     // reference to each waker from inside our executor prevents a waker from
     // being de-allocated from inside an interrupt handler (i.e., deadlock).
     waker_cache: BTreeMap<TaskId, Waker>,
+
+    // Tasks spawned through a `Spawner`, waiting to be moved into `tasks`.
+    spawn_inbox: Arc<SegQueue<Task>>,
+
+    // Number of tasks of each priority, for sizing the ready queues.
+    priority_counts: [usize; Priority::COUNT],
+
+    // Names and statistics of our tasks, for listings.
+    registry: Arc<TaskRegistry>,
+}
+
+// A cloneable handle for spawning tasks onto an executor, which remains usable
+// while the executor is running (e.g., from inside one of its tasks).
+#[derive(Clone)]
+pub struct Spawner {
+    spawn_inbox: Arc<SegQueue<Task>>,
+    registry: Arc<TaskRegistry>,
+}
+
+impl Spawner {
+    // Queues a task; the executor picks it up on its next loop iteration.
+    // Note that pushing to the inbox may allocate, so this must not be called
+    // from an interrupt handler (defer the spawn instead).
+    pub fn spawn(&self, task: Task) {
+        self.spawn_inbox.push(task);
+    }
+
+    // Spawns `future` as a new task, returning a handle to its output.
+    pub fn spawn_with_handle<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (task, handle) = joinable(future);
+
+        self.spawn(task);
+
+        handle
+    }
+
+    // Lists the executor's tasks (not counting any still in the spawn inbox).
+    pub fn snapshot(&self) -> Snapshot {
+        self.registry.snapshot()
+    }
 }
 
 impl Executor {
     pub fn new() -> Self {
         Executor {
             tasks: BTreeMap::<TaskId, Task>::new(),
-            task_queue: Arc::new(ArrayQueue::<TaskId>::new(100)),
+            task_queue: Arc::new(ReadyQueue::new()),
             waker_cache: BTreeMap::<TaskId, Waker>::new(),
+            spawn_inbox: Arc::new(SegQueue::new()),
+            priority_counts: [0; Priority::COUNT],
+            registry: Arc::new(TaskRegistry::new()),
         }
     }
 
+    pub fn spawner(&self) -> Spawner {
+        Spawner {
+            spawn_inbox: self.spawn_inbox.clone(),
+            registry: self.registry.clone(),
+        }
+    }
+
+    // Lists the executor's tasks, e.g. for printing in the style of `ps`.
+    pub fn snapshot(&self) -> Snapshot {
+        self.registry.snapshot()
+    }
+
     pub fn spawn(&mut self, task: Task) {
         let task_id = task.id;
+        let priority = task.priority;
+
+        // The task starts out queued.
+        task.stats.scheduled.store(true, Ordering::Release);
+
+        self.registry
+            .insert(task_id, task.name, priority, task.stats.clone());
 
         if self.tasks.insert(task_id, task).is_some() {
             panic!("Task with ID {} already exists.", task_id.0);
         };
 
-        self.task_queue.push(task_id).expect("Task queue full.");
+        self.priority_counts[priority as usize] += 1;
+
+        // Keeps room in the ready queue for every task (see `ReadyQueue`).
+        self.task_queue
+            .reserve_for(priority, self.priority_counts[priority as usize]);
+
+        self.task_queue.push(task_id, priority);
+    }
+
+    // Spawns `future` as a new task, returning a handle to its output.
+    pub fn spawn_with_handle<F>(&mut self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (task, handle) = joinable(future);
+
+        self.spawn(task);
+
+        handle
     }
 
     pub fn run(&mut self) -> ! {
+        // Only the first executor to run becomes the global one.
+        let _ = GLOBAL_SPAWNER.try_init_once(|| self.spawner());
+
         loop {
+            self.spawn_inbox_tasks();
+
             self.run_ready_tasks();
 
             self.sleep_if_idle();
         }
     }
 
+    // Polls tasks until none are ready to make progress (tasks that are still
+    // waiting on a wakeup remain spawned), then returns.
+    pub fn run_until_idle(&mut self) {
+        while !self.task_queue.is_empty() || !self.spawn_inbox.is_empty() {
+            self.spawn_inbox_tasks();
+
+            self.run_ready_tasks();
+        }
+    }
+
     fn sleep_if_idle(&self) {
         interrupts::disable();
 
-        if self.task_queue.is_empty() {
+        if self.task_queue.is_empty() && self.spawn_inbox.is_empty() {
             enable_and_hlt();
         } else {
             interrupts::enable();
         }
     }
 
+    // Moves any tasks spawned through a `Spawner` into our task map.
+    fn spawn_inbox_tasks(&mut self) {
+        while let Ok(task) = self.spawn_inbox.pop() {
+            self.spawn(task);
+        }
+    }
+
     fn run_ready_tasks(&mut self) {
         // For each task presently in the task queue...
-        while let Ok(task_id) = self.task_queue.pop() {
+        while let Some(task_id) = self.task_queue.pop() {
             // Verify that the task still exists.
             let task = match self.tasks.get_mut(&task_id) {
                 Some(task) => task,
@@ -75,18 +218,51 @@ impl Executor {
             // Retrieves a pre-allocated task waker, or creates a new one and
             // caches it. Note that our `task_queue` is wrapped in an `Arc`, so
             // calls to `clone()` simply increment the reference count.
-            let waker = self
-                .waker_cache
-                .entry(task_id)
-                .or_insert_with(|| TaskWaker::new(task_id, self.task_queue.clone()));
+            let waker = self.waker_cache.entry(task_id).or_insert_with(|| {
+                TaskWaker::new(
+                    task_id,
+                    task.priority,
+                    task.stats.clone(),
+                    self.task_queue.clone(),
+                )
+            });
 
             let mut context = Context::from_waker(waker);
 
-            match task.poll(&mut context) {
+            // Any wakeup from here on (including during the poll) must queue
+            // the task again.
+            task.stats.scheduled.store(false, Ordering::Release);
+
+            watchdog::poll_started(task_id, task.name);
+
+            let poll_start = Instant::now();
+
+            let poll = task.poll(&mut context);
+
+            let poll_end = Instant::now();
+
+            watchdog::poll_finished(task_id, task.name, poll_end - poll_start);
+
+            let poll_cycles = poll_end.as_cycles() - poll_start.as_cycles();
+
+            task.stats.poll_count.fetch_add(1, Ordering::Relaxed);
+            task.stats
+                .poll_cycles
+                .fetch_add(poll_cycles, Ordering::Relaxed);
+
+            match poll {
                 Poll::Ready(()) => {
+                    // Leaves the flag set, so that lingering wakers for this
+                    // (finished) task never queue it again.
+                    task.stats.scheduled.store(true, Ordering::Release);
+
+                    self.priority_counts[task.priority as usize] -= 1;
+
                     self.tasks.remove(&task_id);
 
                     self.waker_cache.remove(&task_id);
+
+                    self.registry.remove(task_id);
                 }
                 Poll::Pending => {}
             }
@@ -96,23 +272,45 @@ impl Executor {
 
 struct TaskWaker {
     task_id: TaskId,
-    task_queue: Arc<ArrayQueue<TaskId>>,
+    priority: Priority,
+
+    // Shared with the task; its `scheduled` flag is set while the task is
+    // sitting in the ready queue, so that repeated wakeups queue it only once.
+    stats: Arc<TaskStats>,
+
+    task_queue: Arc<ReadyQueue>,
 }
 
 impl TaskWaker {
     // Uses the `Waker::from()`` implementation to convert `Self`` to a `Waker`.
     // The inner method creates a RawWakerTable and a RawWaker instance.
-    fn new(task_id: TaskId, task_queue: Arc<ArrayQueue<TaskId>>) -> Waker {
+    fn new(
+        task_id: TaskId,
+        priority: Priority,
+        stats: Arc<TaskStats>,
+        task_queue: Arc<ReadyQueue>,
+    ) -> Waker {
         Waker::from(Arc::new(TaskWaker {
             task_id,
+            priority,
+            stats,
             task_queue,
         }))
     }
 
     fn wake_task(&self) {
-        self.task_queue
-            .push(self.task_id)
-            .expect("Task queue is full!");
+        self.stats
+            .last_woken
+            .store(Instant::now().as_cycles(), Ordering::Relaxed);
+
+        // Only the wakeup that flips the flag gets to queue the task.
+        if !self.stats.scheduled.swap(true, Ordering::AcqRel)
+            && !self.task_queue.push(self.task_id, self.priority)
+        {
+            // The task isn't queued after all, so the next wakeup must try
+            // again.
+            self.stats.scheduled.store(false, Ordering::Release);
+        }
     }
 }
 
@@ -0,0 +1,119 @@
+extern crate alloc;
+
+use alloc::collections::VecDeque;
+
+use spin::Mutex;
+
+use x86_64::instructions::interrupts;
+
+use super::super::{Priority, TaskId};
+
+// The executor's queues of tasks that are ready to be polled, one per
+// priority class.
+//
+// Wakers push to these queues from interrupt handlers, so a push must never
+// allocate. Since each task is queued at most once at a time (see the
+// `scheduled` flag in `TaskStats`), it suffices to keep each queue's capacity at
+// least as large as the number of tasks of its priority; the executor grows
+// it as needed when it spawns, i.e., always from task context. The lock is
+// only ever held with interrupts disabled, so an interrupt handler can't
+// deadlock on it.
+pub(super) struct ReadyQueue {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    queues: [VecDeque<TaskId>; Priority::COUNT],
+
+    // Polls each class may still take before lower classes get a turn; see
+    // `pop()`.
+    credits: [u32; Priority::COUNT],
+}
+
+impl ReadyQueue {
+    pub(super) fn new() -> Self {
+        ReadyQueue {
+            inner: Mutex::new(Inner {
+                queues: Default::default(),
+                credits: Priority::ALL.map(Priority::weight),
+            }),
+        }
+    }
+
+    // Ensures that `task_count` tasks of the given priority (plus any already
+    // queued) can be pushed without reallocating. Must not be called from an
+    // interrupt handler.
+    pub(super) fn reserve_for(&self, priority: Priority, task_count: usize) {
+        interrupts::without_interrupts(|| {
+            let mut inner = self.inner.lock();
+
+            let queue = &mut inner.queues[priority as usize];
+
+            let required = queue.len() + task_count;
+
+            if queue.capacity() < required {
+                let additional = required - queue.len();
+
+                queue.reserve(additional);
+            }
+        });
+    }
+
+    // Queues `task_id`, unless that would mean growing the queue; returns
+    // whether it did. Running out of room means that the capacity invariant
+    // above was broken, but even then we mustn't allocate here.
+    pub(super) fn push(&self, task_id: TaskId, priority: Priority) -> bool {
+        interrupts::without_interrupts(|| {
+            let mut inner = self.inner.lock();
+
+            let queue = &mut inner.queues[priority as usize];
+
+            if queue.len() == queue.capacity() {
+                debug_assert!(false, "Ready queue would grow!");
+
+                return false;
+            }
+
+            queue.push_back(task_id);
+
+            true
+        })
+    }
+
+    // Pops the next task to poll. Higher classes are preferred, but each class
+    // only gets `weight()` polls per round while lower classes are waiting, so
+    // that background tasks still make progress under load.
+    pub(super) fn pop(&self) -> Option<TaskId> {
+        interrupts::without_interrupts(|| {
+            let mut guard = self.inner.lock();
+
+            let inner = &mut *guard;
+
+            if inner.queues.iter().all(VecDeque::is_empty) {
+                return None;
+            }
+
+            loop {
+                for priority in Priority::ALL {
+                    let index = priority as usize;
+
+                    if inner.credits[index] > 0 {
+                        if let Some(task_id) = inner.queues[index].pop_front() {
+                            inner.credits[index] -= 1;
+
+                            return Some(task_id);
+                        }
+                    }
+                }
+
+                // Every class with work has used up its share; starts a new
+                // round.
+                inner.credits = Priority::ALL.map(Priority::weight);
+            }
+        })
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        interrupts::without_interrupts(|| self.inner.lock().queues.iter().all(VecDeque::is_empty))
+    }
+}
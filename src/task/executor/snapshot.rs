@@ -0,0 +1,174 @@
+extern crate alloc;
+
+use core::{fmt, sync::atomic::Ordering, time::Duration};
+
+use alloc::{collections::BTreeMap, format, sync::Arc, vec::Vec};
+
+use spin::Mutex;
+
+use x86_64::instructions::interrupts;
+
+use crate::{
+    task::{Priority, TaskId, TaskStats},
+    time::{tsc, Instant},
+};
+
+// What the executor knows about each spawned task, kept apart from the tasks
+// themselves (which the executor holds mutably while polling), so that task
+// listings can be taken from anywhere.
+pub(super) struct TaskRegistry {
+    // The lock is only held with interrupts disabled, so that a preempted
+    // thread can't leave the executor spinning on it.
+    entries: Mutex<BTreeMap<TaskId, Entry>>,
+}
+
+struct Entry {
+    name: &'static str,
+    priority: Priority,
+    stats: Arc<TaskStats>,
+}
+
+impl TaskRegistry {
+    pub(super) fn new() -> Self {
+        TaskRegistry {
+            entries: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub(super) fn insert(
+        &self,
+        id: TaskId,
+        name: &'static str,
+        priority: Priority,
+        stats: Arc<TaskStats>,
+    ) {
+        let entry = Entry {
+            name,
+            priority,
+            stats,
+        };
+
+        interrupts::without_interrupts(|| self.entries.lock().insert(id, entry));
+    }
+
+    pub(super) fn remove(&self, id: TaskId) {
+        interrupts::without_interrupts(|| self.entries.lock().remove(&id));
+    }
+
+    pub(super) fn snapshot(&self) -> Snapshot {
+        interrupts::without_interrupts(|| {
+            let entries = self.entries.lock();
+
+            let tasks = entries
+                .iter()
+                .map(|(id, entry)| TaskInfo::new(*id, entry))
+                .collect();
+
+            Snapshot {
+                tasks,
+                taken_at: Instant::now(),
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    // Queued, waiting for the executor to poll it.
+    Ready,
+    // Waiting for a wakeup.
+    Waiting,
+}
+
+// A point-in-time copy of one task's bookkeeping.
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    pub id: u64,
+    pub name: &'static str,
+    pub priority: Priority,
+    pub state: TaskState,
+    pub poll_count: u64,
+    pub total_poll_time: Duration,
+    pub last_woken: Option<Instant>,
+}
+
+impl TaskInfo {
+    fn new(id: TaskId, entry: &Entry) -> Self {
+        let stats = &entry.stats;
+
+        let state = if stats.scheduled.load(Ordering::Acquire) {
+            TaskState::Ready
+        } else {
+            TaskState::Waiting
+        };
+
+        let last_woken = match stats.last_woken.load(Ordering::Relaxed) {
+            0 => None,
+            cycles => Some(Instant::from_cycles(cycles)),
+        };
+
+        TaskInfo {
+            id: id.0,
+            name: entry.name,
+            priority: entry.priority,
+            state,
+            poll_count: stats.poll_count.load(Ordering::Relaxed),
+            total_poll_time: tsc::cycles_to_duration(stats.poll_cycles.load(Ordering::Relaxed)),
+            last_woken,
+        }
+    }
+}
+
+// A listing of every task spawned on an executor, in the style of `ps`. Its
+// `Display` implementation prints a table, e.g. with `println!()` (VGA) or
+// `serial_println!()`.
+pub struct Snapshot {
+    tasks: Vec<TaskInfo>,
+    taken_at: Instant,
+}
+
+impl Snapshot {
+    pub fn tasks(&self) -> &[TaskInfo] {
+        &self.tasks
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>4} {:<18} {:<17} {:<7} {:>7} {:>9} LAST WOKEN",
+            "ID", "NAME", "PRIORITY", "STATE", "POLLS", "TIME"
+        )?;
+
+        for task in &self.tasks {
+            let state = match task.state {
+                TaskState::Ready => "ready",
+                TaskState::Waiting => "waiting",
+            };
+
+            write!(
+                f,
+                "{:>4} {:<18.18} {:<17} {:<7} {:>7} {:>7}us ",
+                task.id,
+                task.name,
+                // Formatted first, so that the column width applies.
+                format!("{:?}", task.priority),
+                state,
+                task.poll_count,
+                task.total_poll_time.as_micros(),
+            )?;
+
+            match task.last_woken {
+                Some(woken) => writeln!(
+                    f,
+                    "{}ms ago",
+                    self.taken_at.duration_since(woken).as_millis()
+                )?,
+                None => writeln!(f, "never")?,
+            }
+        }
+
+        write!(f, "{} tasks", self.tasks.len())
+    }
+}
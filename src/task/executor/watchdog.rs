@@ -0,0 +1,153 @@
+use core::{
+    slice, str,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use x86_64::structures::idt::InterruptStackFrame;
+
+use crate::{
+    interrupts::deferred::{self, Work},
+    log,
+    task::TaskId,
+    time,
+};
+
+// Keeps an eye on the executor's polls. A task's `poll()` must return quickly,
+// since nothing else on the executor runs until it does; polls that take
+// longer than the slow-poll threshold are logged once they return, and the
+// timer interrupt flags any poll that's still running after the watchdog's
+// tick limit (i.e., one that may never return), deferring its report.
+//
+// Only one poll is tracked at a time, so this assumes a single executor whose
+// polls don't nest: if a task runs an executor of its own (e.g., through
+// `run_until_idle()`), the inner polls replace the outer one, and the rest of
+// the outer poll goes unwatched once they finish.
+
+// Sentinel value of CURRENT_TASK while no poll is in progress.
+const NO_TASK: u64 = u64::MAX;
+
+// The task being polled, and its name (split in two, so it fits in atomics).
+static CURRENT_TASK: AtomicU64 = AtomicU64::new(NO_TASK);
+static CURRENT_NAME_PTR: AtomicPtr<u8> = AtomicPtr::new(core::ptr::null_mut());
+static CURRENT_NAME_LEN: AtomicUsize = AtomicUsize::new(0);
+
+// Timer tick at which the current poll started.
+static POLL_STARTED: AtomicU64 = AtomicU64::new(0);
+
+// Set once the current poll has been reported, so it's reported only once.
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+// The most recently reported poll, for `report_stuck_poll()`.
+static STUCK_NAME_PTR: AtomicPtr<u8> = AtomicPtr::new(core::ptr::null_mut());
+static STUCK_NAME_LEN: AtomicUsize = AtomicUsize::new(0);
+static STUCK_TICKS: AtomicU64 = AtomicU64::new(0);
+static STUCK_RIP: AtomicU64 = AtomicU64::new(0);
+
+static SLOW_POLL_THRESHOLD_MICROS: AtomicU64 = AtomicU64::new(10_000);
+
+// 0 disables the watchdog.
+static WATCHDOG_TICKS: AtomicU64 = AtomicU64::new(time::TICKS_PER_SECOND);
+
+static SLOW_POLLS: AtomicU64 = AtomicU64::new(0);
+static STUCK_POLLS: AtomicU64 = AtomicU64::new(0);
+
+// Sets how long a poll may take before it's logged as slow.
+pub fn set_slow_poll_threshold(threshold: Duration) {
+    SLOW_POLL_THRESHOLD_MICROS.store(threshold.as_micros() as u64, Ordering::Relaxed);
+}
+
+// Sets how many timer ticks a poll may run before the watchdog reports it;
+// 0 disables the watchdog.
+pub fn set_watchdog_ticks(ticks: u64) {
+    WATCHDOG_TICKS.store(ticks, Ordering::Relaxed);
+}
+
+// Number of polls logged as slow since boot.
+pub fn slow_poll_count() -> u64 {
+    SLOW_POLLS.load(Ordering::Relaxed)
+}
+
+// Number of polls the watchdog has reported as stuck since boot.
+pub fn stuck_poll_count() -> u64 {
+    STUCK_POLLS.load(Ordering::Relaxed)
+}
+
+pub(super) fn poll_started(task_id: TaskId, name: &'static str) {
+    CURRENT_NAME_PTR.store(name.as_ptr() as *mut u8, Ordering::Relaxed);
+    CURRENT_NAME_LEN.store(name.len(), Ordering::Relaxed);
+
+    POLL_STARTED.store(time::ticks(), Ordering::Relaxed);
+
+    REPORTED.store(false, Ordering::Relaxed);
+
+    // Published last, so the watchdog never sees a half-updated task.
+    CURRENT_TASK.store(task_id.0, Ordering::Release);
+}
+
+pub(super) fn poll_finished(task_id: TaskId, name: &'static str, duration: Duration) {
+    CURRENT_TASK.store(NO_TASK, Ordering::Release);
+
+    if duration.as_micros() as u64 > SLOW_POLL_THRESHOLD_MICROS.load(Ordering::Relaxed) {
+        SLOW_POLLS.fetch_add(1, Ordering::Relaxed);
+
+        log!(
+            "WARNING: task {} ({}) was polled for {:?}",
+            task_id.0, name, duration
+        );
+    }
+}
+
+// Called by the timer interrupt handler, once per tick. The stack frame is
+// that of whatever the interrupt preempted, which is the stuck poll itself
+// unless other kernel threads are running.
+pub(crate) fn check(stack_frame: &InterruptStackFrame) {
+    let limit = WATCHDOG_TICKS.load(Ordering::Relaxed);
+
+    let task_id = CURRENT_TASK.load(Ordering::Acquire);
+
+    if limit == 0 || task_id == NO_TASK || REPORTED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let stuck_for = time::ticks() - POLL_STARTED.load(Ordering::Relaxed);
+
+    if stuck_for < limit {
+        return;
+    }
+
+    REPORTED.store(true, Ordering::Relaxed);
+
+    STUCK_POLLS.fetch_add(1, Ordering::Relaxed);
+
+    STUCK_NAME_PTR.store(CURRENT_NAME_PTR.load(Ordering::Relaxed), Ordering::Relaxed);
+    STUCK_NAME_LEN.store(CURRENT_NAME_LEN.load(Ordering::Relaxed), Ordering::Relaxed);
+    STUCK_TICKS.store(stuck_for, Ordering::Relaxed);
+    STUCK_RIP.store(stack_frame.instruction_pointer.as_u64(), Ordering::Relaxed);
+
+    // Printing is left to task context. Note that the report can only run once
+    // something drains the deferred work queue, i.e., once the stuck poll
+    // returns, unless another executor (e.g., on a kernel thread) drains it.
+    deferred::schedule(Work::new(report_stuck_poll, task_id));
+}
+
+// Deferred by `check()`.
+fn report_stuck_poll(task_id: u64) {
+    // Task names are always `&'static str`s, stored by `poll_started()`.
+    let name = unsafe {
+        let bytes = slice::from_raw_parts(
+            STUCK_NAME_PTR.load(Ordering::Relaxed),
+            STUCK_NAME_LEN.load(Ordering::Relaxed),
+        );
+
+        str::from_utf8_unchecked(bytes)
+    };
+
+    log!(
+        "WATCHDOG: task {} ({}) was polled for over {} ticks; interrupted at RIP {:#x}",
+        task_id,
+        name,
+        STUCK_TICKS.load(Ordering::Relaxed),
+        STUCK_RIP.load(Ordering::Relaxed)
+    );
+}
@@ -0,0 +1,234 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+extern crate alloc;
+
+use alloc::{boxed::Box, sync::Arc};
+
+use spin::Mutex;
+
+use x86_64::instructions::interrupts;
+
+use super::{default_name, Task};
+
+// Why a task didn't produce an output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinError {
+    // The task was aborted through its `JoinHandle`.
+    Cancelled,
+    // The task was dropped before completing, without being aborted, e.g.,
+    // because its executor was dropped. (The kernel is built with
+    // `panic = "abort"`, so a panicking task never gets this far.)
+    Dropped,
+}
+
+enum JoinStatus<T> {
+    Running,
+    Completed(T),
+    Failed(JoinError),
+    // The output (or error) has already been handed to the `JoinHandle`.
+    Taken,
+}
+
+struct JoinState<T> {
+    status: JoinStatus<T>,
+    abort_requested: bool,
+
+    // Waker for whichever task is awaiting the `JoinHandle`.
+    join_waker: Option<Waker>,
+
+    // Waker for the task itself, so that `abort()` can get it polled (and
+    // thereby torn down) promptly.
+    task_waker: Option<Waker>,
+}
+
+// Wraps a task's future, publishing its output (or its cancellation) to the
+// state it shares with a `JoinHandle`.
+struct Joinable<F: Future> {
+    future: Pin<Box<F>>,
+    state: Arc<Mutex<JoinState<F::Output>>>,
+}
+
+impl<F: Future> Future for Joinable<F> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let aborted = interrupts::without_interrupts(|| {
+            let mut state = self.state.lock();
+
+            if state.abort_requested {
+                state.status = JoinStatus::Failed(JoinError::Cancelled);
+
+                if let Some(waker) = state.join_waker.take() {
+                    waker.wake();
+                }
+
+                return true;
+            }
+
+            state.task_waker = Some(cx.waker().clone());
+
+            false
+        });
+
+        if aborted {
+            return Poll::Ready(());
+        }
+
+        // We mustn't hold the lock here, in case the future aborts itself.
+        let output = match self.future.as_mut().poll(cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        interrupts::without_interrupts(|| {
+            let mut state = self.state.lock();
+
+            state.status = JoinStatus::Completed(output);
+
+            if let Some(waker) = state.join_waker.take() {
+                waker.wake();
+            }
+
+            Poll::Ready(())
+        })
+    }
+}
+
+impl<F: Future> Drop for Joinable<F> {
+    fn drop(&mut self) {
+        interrupts::without_interrupts(|| {
+            let mut state = self.state.lock();
+
+            if let JoinStatus::Running = state.status {
+                state.status = JoinStatus::Failed(JoinError::Dropped);
+
+                if let Some(waker) = state.join_waker.take() {
+                    waker.wake();
+                }
+            }
+        });
+    }
+}
+
+// A handle to a spawned task, which resolves to the task's output. Dropping
+// the handle detaches the task (it keeps running); use `abort()` to cancel it.
+pub struct JoinHandle<T> {
+    state: Arc<Mutex<JoinState<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    // Requests that the task be cancelled; its future is dropped the next time
+    // the executor would have polled it. Has no effect on a finished task.
+    pub fn abort(&self) {
+        self.state.abort();
+    }
+
+    // Whether the task has completed, been cancelled, or been torn down.
+    pub fn is_finished(&self) -> bool {
+        self.state.is_finished()
+    }
+}
+
+impl<T: Send + 'static> JoinHandle<T> {
+    // Returns a cloneable handle that can abort the task, without being able
+    // to await its output.
+    pub fn abort_handle(&self) -> AbortHandle {
+        AbortHandle {
+            state: self.state.clone(),
+        }
+    }
+}
+
+// The output-agnostic part of a task's join state, so that `AbortHandle`s
+// needn't know the task's output type.
+trait Abortable {
+    fn abort(&self);
+    fn is_finished(&self) -> bool;
+}
+
+impl<T> Abortable for Mutex<JoinState<T>> {
+    fn abort(&self) {
+        let task_waker = interrupts::without_interrupts(|| {
+            let mut state = self.lock();
+
+            state.abort_requested = true;
+
+            state.task_waker.take()
+        });
+
+        if let Some(waker) = task_waker {
+            waker.wake();
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        interrupts::without_interrupts(|| !matches!(self.lock().status, JoinStatus::Running))
+    }
+}
+
+// A cloneable handle for aborting a task (see `JoinHandle::abort_handle()`).
+#[derive(Clone)]
+pub struct AbortHandle {
+    state: Arc<dyn Abortable + Send + Sync>,
+}
+
+impl AbortHandle {
+    // Same as `JoinHandle::abort()`.
+    pub fn abort(&self) {
+        self.state.abort();
+    }
+
+    // Same as `JoinHandle::is_finished()`.
+    pub fn is_finished(&self) -> bool {
+        self.state.is_finished()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        interrupts::without_interrupts(|| {
+            let mut state = self.state.lock();
+
+            match core::mem::replace(&mut state.status, JoinStatus::Taken) {
+                JoinStatus::Running => {
+                    state.status = JoinStatus::Running;
+                    state.join_waker = Some(cx.waker().clone());
+
+                    Poll::Pending
+                }
+                JoinStatus::Completed(output) => Poll::Ready(Ok(output)),
+                JoinStatus::Failed(error) => Poll::Ready(Err(error)),
+                JoinStatus::Taken => panic!("JoinHandle polled after completion."),
+            }
+        })
+    }
+}
+
+// Wraps `future` in a `Task`, paired with a `JoinHandle` for its output.
+pub fn joinable<F>(future: F) -> (Task, JoinHandle<F::Output>)
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let state = Arc::new(Mutex::new(JoinState {
+        status: JoinStatus::Running,
+        abort_requested: false,
+        join_waker: None,
+        task_waker: None,
+    }));
+
+    // Named after the wrapped future, rather than after `Joinable`.
+    let task = Task::new(Joinable {
+        future: Box::pin(future),
+        state: state.clone(),
+    })
+    .named(default_name::<F>());
+
+    (task, JoinHandle { state })
+}
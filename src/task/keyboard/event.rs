@@ -0,0 +1,344 @@
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use conquer_once::spin::OnceCell;
+
+use futures_util::stream::{Stream, StreamExt};
+
+use pc_keyboard::{
+    layouts, DecodedKey, Error, EventDecoder, KeyCode, KeyEvent, KeyState, ScancodeSet,
+    ScancodeSet1, ScancodeSet2,
+};
+
+use crate::{
+    println,
+    ps2::{self, Leds, ScancodeMode},
+    task::channel::broadcast::{self, RecvError},
+};
+
+use super::{hotkey, layout, ScancodeStream};
+
+// How many events a subscriber may fall behind before it starts missing them.
+const EVENT_CAPACITY: usize = 64;
+
+// State of the modifier and lock keys when an event occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyModifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    // The Windows (or "super") key.
+    pub meta: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+}
+
+// Num Lock starts out on, matching both the decoder (which starts with it on)
+// and the keyboard's LEDs (which `ps2::init()` lights to match).
+impl Default for KeyModifiers {
+    fn default() -> Self {
+        KeyModifiers {
+            shift: false,
+            ctrl: false,
+            alt: false,
+            meta: false,
+            caps_lock: false,
+            num_lock: true,
+            scroll_lock: false,
+        }
+    }
+}
+
+impl KeyModifiers {
+    // Applies a key event (other than a repeat of a lock key).
+    fn update(&mut self, code: KeyCode, pressed: bool) {
+        match code {
+            KeyCode::LShift | KeyCode::RShift => self.shift = pressed,
+            KeyCode::LControl | KeyCode::RControl => self.ctrl = pressed,
+            KeyCode::LAlt | KeyCode::RAltGr => self.alt = pressed,
+            KeyCode::LWin | KeyCode::RWin => self.meta = pressed,
+            KeyCode::CapsLock if pressed => self.caps_lock = !self.caps_lock,
+            KeyCode::NumpadLock if pressed => self.num_lock = !self.num_lock,
+            KeyCode::ScrollLock if pressed => self.scroll_lock = !self.scroll_lock,
+            _ => {}
+        }
+    }
+
+    fn leds(&self) -> Leds {
+        Leds {
+            scroll_lock: self.scroll_lock,
+            num_lock: self.num_lock,
+            caps_lock: self.caps_lock,
+        }
+    }
+}
+
+// Index of a lock key, for tracking whether it's held.
+fn lock_key_index(code: KeyCode) -> Option<usize> {
+    match code {
+        KeyCode::CapsLock => Some(0),
+        KeyCode::NumpadLock => Some(1),
+        KeyCode::ScrollLock => Some(2),
+        _ => None,
+    }
+}
+
+fn is_modifier_key(code: KeyCode) -> bool {
+    matches!(
+        code,
+        KeyCode::LShift
+            | KeyCode::RShift
+            | KeyCode::LControl
+            | KeyCode::RControl
+            | KeyCode::LAlt
+            | KeyCode::RAltGr
+            | KeyCode::LWin
+            | KeyCode::RWin
+            | KeyCode::CapsLock
+            | KeyCode::NumpadLock
+            | KeyCode::ScrollLock
+    )
+}
+
+// A decoded key press or release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyboardEvent {
+    // The physical key.
+    pub code: KeyCode,
+    // False if the key was released.
+    pub pressed: bool,
+    // Modifier state after this event was applied.
+    pub modifiers: KeyModifiers,
+    // The character the key produced under the current layout, if any.
+    pub unicode: Option<char>,
+}
+
+impl KeyboardEvent {
+    // Whether this is one of the keys tracked in `KeyModifiers`.
+    pub fn is_modifier(&self) -> bool {
+        is_modifier_key(self.code)
+    }
+}
+
+// Every subscriber's events come from this channel, so scancodes are decoded
+// only once, however many subscribers there are.
+static EVENTS: OnceCell<broadcast::Sender<KeyboardEvent>> = OnceCell::uninit();
+
+fn events() -> &'static broadcast::Sender<KeyboardEvent> {
+    EVENTS.get_or_init(|| broadcast::channel(EVENT_CAPACITY).0)
+}
+
+// A stream of keyboard events. Any number of these may exist at once; each
+// sees every event that occurs after it's created (unless it falls more than
+// EVENT_CAPACITY events behind, in which case it skips the missed events).
+pub struct KeyEventStream {
+    receiver: broadcast::Receiver<KeyboardEvent>,
+}
+
+impl KeyEventStream {
+    pub fn new() -> Self {
+        KeyEventStream {
+            receiver: events().subscribe(),
+        }
+    }
+}
+
+impl Default for KeyEventStream {
+    fn default() -> Self {
+        KeyEventStream::new()
+    }
+}
+
+impl Stream for KeyEventStream {
+    type Item = KeyboardEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<KeyboardEvent>> {
+        let receiver = &mut self.get_mut().receiver;
+
+        loop {
+            match receiver.poll_recv(cx) {
+                Poll::Ready(Ok(event)) => return Poll::Ready(Some(event)),
+                Poll::Ready(Err(RecvError::Lagged(_))) => continue,
+                Poll::Ready(Err(RecvError::Closed)) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+// Whichever scancode set the PS/2 controller delivers.
+enum Scancodes {
+    Set1(ScancodeSet1),
+    Set2(ScancodeSet2),
+}
+
+// Decodes scancodes under the current layout and control-key settings.
+// Changes to those settings are applied to the existing decoder, since a new
+// one would forget which modifiers are held (and the state of the lock keys).
+struct KeyDecoder {
+    scancodes: Scancodes,
+    event_decoder: EventDecoder<layouts::AnyLayout>,
+
+    // The `layout::generation()` that `event_decoder` is up to date with.
+    generation: u64,
+}
+
+impl KeyDecoder {
+    fn new() -> Self {
+        let scancodes = match ps2::scancode_mode() {
+            Some(ScancodeMode::Set2) => Scancodes::Set2(ScancodeSet2::new()),
+            // Translated by the controller, or left as the firmware set it up.
+            _ => Scancodes::Set1(ScancodeSet1::new()),
+        };
+
+        KeyDecoder {
+            scancodes,
+            event_decoder: EventDecoder::new(
+                layout::current_layout().to_any(),
+                layout::control_handling(),
+            ),
+            generation: layout::generation(),
+        }
+    }
+
+    fn add_byte(&mut self, byte: u8) -> Result<Option<KeyEvent>, Error> {
+        match &mut self.scancodes {
+            Scancodes::Set1(scancode_set) => scancode_set.advance_state(byte),
+            Scancodes::Set2(scancode_set) => scancode_set.advance_state(byte),
+        }
+    }
+
+    fn process_keyevent(&mut self, event: KeyEvent) -> Option<DecodedKey> {
+        let generation = layout::generation();
+
+        if generation != self.generation {
+            self.generation = generation;
+
+            self.event_decoder
+                .change_layout(layout::current_layout().to_any());
+            self.event_decoder
+                .set_ctrl_handling(layout::control_handling());
+        }
+
+        self.event_decoder.process_keyevent(event)
+    }
+}
+
+// Ctrl+Alt+L cycles through the keyboard layouts.
+fn is_layout_hotkey(event: &KeyboardEvent) -> bool {
+    event.modifiers.ctrl && event.modifiers.alt && event.code == KeyCode::L && event.pressed
+}
+
+// The keyboard service: the sole consumer of raw scancodes, which it decodes
+// (under the configured layout) and publishes to every `KeyEventStream`.
+pub async fn keyboard_task() {
+    let mut scancodes = ScancodeStream::new();
+
+    let mut keyboard = KeyDecoder::new();
+
+    let mut modifiers = KeyModifiers::default();
+
+    // Keys repeat while held (as further presses), but holding a lock key
+    // should only toggle it once.
+    let mut lock_held = [false; 3];
+
+    let mut sysrq_held = false;
+
+    let sender = events();
+
+    while let Some(scancode) = scancodes.next().await {
+        let key_event = match keyboard.add_byte(scancode) {
+            Ok(Some(key_event)) => key_event,
+            _ => continue,
+        };
+
+        let code = key_event.code;
+        let pressed = key_event.state != KeyState::Up;
+
+        if hotkey::is_sysrq_key(code) {
+            sysrq_held = pressed;
+        }
+
+        if let Some(index) = lock_key_index(code) {
+            let repeat = pressed && lock_held[index];
+
+            lock_held[index] = pressed;
+
+            // Neither our modifiers nor the decoder (which tracks the lock keys
+            // too) may see a repeat, or they'd toggle it again.
+            if repeat {
+                continue;
+            }
+        }
+
+        modifiers.update(code, pressed);
+
+        if lock_key_index(code).is_some() {
+            // Sending the command means waiting for the keyboard's reply, which
+            // mustn't happen while this task is being polled.
+            ps2::set_leds_deferred(modifiers.leds());
+        }
+
+        let unicode = match keyboard.process_keyevent(key_event) {
+            Some(DecodedKey::Unicode(character)) => Some(character),
+            _ => None,
+        };
+
+        let event = KeyboardEvent {
+            code,
+            pressed,
+            modifiers,
+            unicode,
+        };
+
+        // Global hotkeys come first, so they work whatever else is going on.
+        if hotkey::handle(&event, sysrq_held) {
+            continue;
+        }
+
+        if is_layout_hotkey(&event) {
+            let layout = layout::cycle_layout();
+
+            println!("\nKeyboard layout: {}", layout.name());
+
+            continue;
+        }
+
+        sender.send(event);
+    }
+}
+
+#[test_case]
+fn test_caps_lock_toggles_on_press() {
+    let mut modifiers = KeyModifiers::default();
+
+    modifiers.update(KeyCode::CapsLock, true);
+    modifiers.update(KeyCode::CapsLock, false);
+
+    assert!(modifiers.caps_lock);
+
+    modifiers.update(KeyCode::CapsLock, true);
+
+    assert!(!modifiers.caps_lock);
+}
+
+#[test_case]
+fn test_num_lock_starts_on() {
+    let mut modifiers = KeyModifiers::default();
+
+    assert!(modifiers.num_lock);
+    assert_eq!(
+        modifiers.leds(),
+        Leds {
+            num_lock: true,
+            ..Leds::default()
+        }
+    );
+
+    modifiers.update(KeyCode::NumpadLock, true);
+
+    assert!(!modifiers.num_lock);
+}
@@ -0,0 +1,170 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use pc_keyboard::KeyCode;
+
+use spin::Mutex;
+
+use x86_64::instructions::interrupts;
+
+use crate::{allocator, interrupts::stats, memory, println, ps2, task::executor};
+
+use super::KeyboardEvent;
+
+// Global hotkeys: while Alt and SysRq are held, key presses trigger their
+// registered actions instead of being delivered to `KeyEventStream`s. They're
+// handled by the keyboard service itself, so they work whether or not anyone
+// is reading input.
+
+// Actions run on the keyboard service's task, so they shouldn't block.
+pub type Action = fn();
+
+#[derive(Clone, Copy)]
+struct Hotkey {
+    key: KeyCode,
+    description: &'static str,
+    action: Action,
+}
+
+static HOTKEYS: Mutex<Vec<Hotkey>> = Mutex::new(Vec::new());
+
+// Binds Alt+SysRq+`key` to `action`, replacing any existing binding for `key`.
+pub fn register(key: KeyCode, description: &'static str, action: Action) {
+    interrupts::without_interrupts(|| {
+        let mut hotkeys = HOTKEYS.lock();
+
+        hotkeys.retain(|hotkey| hotkey.key != key);
+
+        hotkeys.push(Hotkey {
+            key,
+            description,
+            action,
+        });
+    });
+}
+
+pub fn unregister(key: KeyCode) {
+    interrupts::without_interrupts(|| HOTKEYS.lock().retain(|hotkey| hotkey.key != key));
+}
+
+fn find(key: KeyCode) -> Option<Hotkey> {
+    interrupts::without_interrupts(|| {
+        HOTKEYS
+            .lock()
+            .iter()
+            .find(|hotkey| hotkey.key == key)
+            .copied()
+    })
+}
+
+// Alt+PrintScreen is SysRq; depending on the scancode set, it may be decoded
+// as either key.
+pub(super) fn is_sysrq_key(code: KeyCode) -> bool {
+    matches!(code, KeyCode::SysRq | KeyCode::PrintScreen)
+}
+
+// Runs the action for `event`, if it's a hotkey. Returns whether the event was
+// consumed (i.e., shouldn't be delivered), which every key is while Alt and
+// SysRq are held.
+pub(super) fn handle(event: &KeyboardEvent, sysrq_held: bool) -> bool {
+    if !sysrq_held || !event.modifiers.alt {
+        return false;
+    }
+
+    if event.pressed && !event.is_modifier() && !is_sysrq_key(event.code) {
+        // The lock isn't held while the action runs, so actions may
+        // (un)register hotkeys themselves.
+        match find(event.code) {
+            Some(hotkey) => (hotkey.action)(),
+            None => println!("\nSysRq: no action for {:?} (H for help)", event.code),
+        }
+    }
+
+    true
+}
+
+fn print_help() {
+    let hotkeys = interrupts::without_interrupts(|| HOTKEYS.lock().clone());
+
+    println!("\nSysRq hotkeys (hold Alt+SysRq):");
+
+    for hotkey in hotkeys {
+        println!("  {:?}: {}", hotkey.key, hotkey.description);
+    }
+}
+
+fn dump_tasks() {
+    match executor::snapshot() {
+        Some(snapshot) => println!("\n{}", snapshot),
+        None => println!("\nSysRq: no executor is running"),
+    }
+}
+
+fn dump_heap() {
+    println!("\n{}", allocator::stats());
+}
+
+fn dump_interrupts() {
+    println!("\n{}", stats::snapshot());
+}
+
+fn dump_page_tables() {
+    println!();
+
+    memory::dump_page_tables();
+}
+
+fn reboot() {
+    println!("\nSysRq: rebooting");
+
+    ps2::reboot();
+}
+
+fn crash() {
+    panic!("SysRq: crash requested from the keyboard");
+}
+
+// Registers the standard diagnostic hotkeys.
+pub fn register_defaults() {
+    register(KeyCode::H, "show this help", print_help);
+    register(KeyCode::T, "dump tasks", dump_tasks);
+    register(KeyCode::M, "dump heap statistics", dump_heap);
+    register(KeyCode::I, "dump interrupt counters", dump_interrupts);
+    register(KeyCode::P, "dump page tables", dump_page_tables);
+    register(KeyCode::B, "reboot", reboot);
+    register(KeyCode::C, "crash (panic)", crash);
+}
+
+#[test_case]
+fn test_hotkeys_run_while_alt_and_sysrq_are_held() {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use super::KeyModifiers;
+
+    static RAN: AtomicBool = AtomicBool::new(false);
+
+    register(KeyCode::F12, "test", || RAN.store(true, Ordering::Relaxed));
+
+    let mut event = KeyboardEvent {
+        code: KeyCode::F12,
+        pressed: true,
+        modifiers: KeyModifiers {
+            alt: true,
+            ..KeyModifiers::default()
+        },
+        unicode: None,
+    };
+
+    assert!(!handle(&event, false));
+    assert!(!RAN.load(Ordering::Relaxed));
+
+    assert!(handle(&event, true));
+    assert!(RAN.load(Ordering::Relaxed));
+
+    event.modifiers.alt = false;
+
+    assert!(!handle(&event, true));
+
+    unregister(KeyCode::F12);
+}
@@ -0,0 +1,147 @@
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use pc_keyboard::{layouts, HandleControl};
+
+use crate::task::sync::Notify;
+
+// Keyboard settings shared between the keyboard task and its consumers. The
+// keyboard task picks up changes on the next scancode it decodes.
+
+// Physical keyboard layouts supported by `pc_keyboard`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Layout {
+    Us104 = 0,
+    Uk105 = 1,
+    Dvorak104 = 2,
+    Azerty = 3,
+    De105 = 4,
+    Jis109 = 5,
+    Colemak = 6,
+}
+
+impl Layout {
+    // The order in which the layout hotkey cycles through layouts.
+    pub const ALL: [Layout; 7] = [
+        Layout::Us104,
+        Layout::Uk105,
+        Layout::Dvorak104,
+        Layout::Azerty,
+        Layout::De105,
+        Layout::Jis109,
+        Layout::Colemak,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Us104 => "US (104-key)",
+            Layout::Uk105 => "UK (105-key)",
+            Layout::Dvorak104 => "Dvorak (104-key)",
+            Layout::Azerty => "French AZERTY",
+            Layout::De105 => "German (105-key)",
+            Layout::Jis109 => "Japanese (109-key)",
+            Layout::Colemak => "Colemak",
+        }
+    }
+
+    // Returns the layout after this one, wrapping around.
+    pub fn next(self) -> Layout {
+        Layout::ALL[(self as usize + 1) % Layout::ALL.len()]
+    }
+
+    fn from_u8(value: u8) -> Layout {
+        Layout::ALL[value as usize]
+    }
+
+    pub(super) fn to_any(self) -> layouts::AnyLayout {
+        match self {
+            Layout::Us104 => layouts::AnyLayout::Us104Key(layouts::Us104Key),
+            Layout::Uk105 => layouts::AnyLayout::Uk105Key(layouts::Uk105Key),
+            Layout::Dvorak104 => layouts::AnyLayout::Dvorak104Key(layouts::Dvorak104Key),
+            Layout::Azerty => layouts::AnyLayout::Azerty(layouts::Azerty),
+            Layout::De105 => layouts::AnyLayout::De105Key(layouts::De105Key),
+            Layout::Jis109 => layouts::AnyLayout::Jis109Key(layouts::Jis109Key),
+            Layout::Colemak => layouts::AnyLayout::Colemak(layouts::Colemak),
+        }
+    }
+}
+
+static LAYOUT: AtomicU8 = AtomicU8::new(Layout::Us104 as u8);
+
+// Whether Ctrl+letter produces the matching control character (e.g., Ctrl+C
+// is U+0003); 0 for `HandleControl::Ignore`, 1 for `MapLettersToUnicode`.
+static CONTROL_HANDLING: AtomicU8 = AtomicU8::new(0);
+
+// Bumped by every settings change, so the keyboard task can spot them cheaply.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+static CHANGED: Notify = Notify::new();
+
+pub fn current_layout() -> Layout {
+    Layout::from_u8(LAYOUT.load(Ordering::Relaxed))
+}
+
+pub fn set_layout(layout: Layout) {
+    LAYOUT.store(layout as u8, Ordering::Relaxed);
+
+    settings_changed();
+}
+
+// Switches to the next layout (see `Layout::ALL`), returning it.
+pub fn cycle_layout() -> Layout {
+    let layout = current_layout().next();
+
+    set_layout(layout);
+
+    layout
+}
+
+pub fn control_handling() -> HandleControl {
+    match CONTROL_HANDLING.load(Ordering::Relaxed) {
+        0 => HandleControl::Ignore,
+        _ => HandleControl::MapLettersToUnicode,
+    }
+}
+
+pub fn set_control_handling(handling: HandleControl) {
+    let value = match handling {
+        HandleControl::Ignore => 0,
+        HandleControl::MapLettersToUnicode => 1,
+    };
+
+    CONTROL_HANDLING.store(value, Ordering::Relaxed);
+
+    settings_changed();
+}
+
+// Waits for the next change to the keyboard settings, returning the layout in
+// effect afterwards.
+pub async fn layout_changed() -> Layout {
+    CHANGED.notified().await;
+
+    current_layout()
+}
+
+pub(super) fn generation() -> u64 {
+    GENERATION.load(Ordering::Acquire)
+}
+
+fn settings_changed() {
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+
+    CHANGED.notify_waiters();
+}
+
+#[test_case]
+fn test_cycle_layout_wraps_around() {
+    let original = current_layout();
+
+    let before = generation();
+
+    set_layout(Layout::Colemak);
+
+    assert_eq!(cycle_layout(), Layout::Us104);
+    assert!(generation() > before);
+
+    set_layout(original);
+}
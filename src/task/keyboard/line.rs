@@ -0,0 +1,351 @@
+extern crate alloc;
+
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+
+use futures_util::stream::StreamExt;
+
+use pc_keyboard::KeyCode;
+
+use spin::Mutex;
+
+use x86_64::instructions::interrupts;
+
+use crate::{
+    println,
+    vga_buffer::{cp437, BUFFER_WIDTH, WRITER},
+};
+
+use super::{KeyEventStream, KeyboardEvent};
+
+// A line discipline: turns keyboard events into edited lines of text, echoing
+// the line being edited on the VGA console.
+
+// Number of previous lines kept for the up/down arrows.
+const HISTORY_CAPACITY: usize = 32;
+
+// Lines entered through `LineReader::read_line()`, oldest first.
+static HISTORY: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+// The state of one line being edited, independent of how it's displayed.
+pub struct LineEditor {
+    line: Vec<char>,
+
+    // Index into `line` at which the next character will be inserted.
+    cursor: usize,
+
+    // The line can't grow past this many characters.
+    max_len: usize,
+
+    // Snapshot of the history at the start of the edit, and our position in it
+    // while browsing (`None` while editing a new line).
+    history: Vec<String>,
+    history_index: Option<usize>,
+
+    // The new line, saved while we're browsing the history.
+    draft: Vec<char>,
+}
+
+impl LineEditor {
+    pub fn new(max_len: usize, history: Vec<String>) -> Self {
+        LineEditor {
+            line: Vec::new(),
+            cursor: 0,
+            max_len,
+            history,
+            history_index: None,
+            draft: Vec::new(),
+        }
+    }
+
+    pub fn line(&self) -> &[char] {
+        &self.line
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    // Applies a key event, returning the finished line once Enter is pressed.
+    pub fn handle(&mut self, event: &KeyboardEvent) -> Option<String> {
+        if !event.pressed {
+            return None;
+        }
+
+        let ctrl = event.modifiers.ctrl;
+
+        match (event.code, event.unicode) {
+            (KeyCode::Return, _) | (_, Some('\n')) => {
+                return Some(self.line.iter().collect());
+            }
+            (KeyCode::Backspace, _) | (_, Some('\u{8}')) => self.backspace(),
+            (KeyCode::Delete, _) | (_, Some('\u{7f}')) => self.delete(),
+            (KeyCode::ArrowLeft, _) => self.cursor = self.cursor.saturating_sub(1),
+            (KeyCode::ArrowRight, _) => self.cursor = (self.cursor + 1).min(self.line.len()),
+            (KeyCode::Home, _) => self.cursor = 0,
+            (KeyCode::End, _) => self.cursor = self.line.len(),
+            (KeyCode::ArrowUp, _) => self.history_previous(),
+            (KeyCode::ArrowDown, _) => self.history_next(),
+            // Depending on the control-key handling, Ctrl+U and Ctrl+W arrive
+            // either as letters with Ctrl held, or as control characters.
+            (KeyCode::U, _) if ctrl => self.kill_line(),
+            (_, Some('\u{15}')) => self.kill_line(),
+            (KeyCode::W, _) if ctrl => self.kill_word(),
+            (_, Some('\u{17}')) => self.kill_word(),
+            (_, Some(character)) if !ctrl && !character.is_control() => self.insert(character),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn insert(&mut self, character: char) {
+        if self.line.len() < self.max_len {
+            self.line.insert(self.cursor, character);
+
+            self.cursor += 1;
+        }
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+
+            self.line.remove(self.cursor);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.line.len() {
+            self.line.remove(self.cursor);
+        }
+    }
+
+    // Deletes everything before the cursor.
+    fn kill_line(&mut self) {
+        self.line.drain(..self.cursor);
+
+        self.cursor = 0;
+    }
+
+    // Deletes the word before the cursor, along with any spaces between the
+    // word and the cursor.
+    fn kill_word(&mut self) {
+        let mut start = self.cursor;
+
+        while start > 0 && self.line[start - 1].is_whitespace() {
+            start -= 1;
+        }
+
+        while start > 0 && !self.line[start - 1].is_whitespace() {
+            start -= 1;
+        }
+
+        self.line.drain(start..self.cursor);
+
+        self.cursor = start;
+    }
+
+    fn history_previous(&mut self) {
+        let index = match self.history_index {
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = self.line.clone();
+
+                self.history.len() - 1
+            }
+            Some(0) => return,
+            Some(index) => index - 1,
+        };
+
+        self.history_index = Some(index);
+
+        self.replace_line(self.history[index].chars().collect());
+    }
+
+    fn history_next(&mut self) {
+        match self.history_index {
+            None => {}
+            Some(index) if index + 1 < self.history.len() => {
+                self.history_index = Some(index + 1);
+
+                self.replace_line(self.history[index + 1].chars().collect());
+            }
+            Some(_) => {
+                self.history_index = None;
+
+                let draft = core::mem::take(&mut self.draft);
+
+                self.replace_line(draft);
+            }
+        }
+    }
+
+    fn replace_line(&mut self, mut line: Vec<char>) {
+        line.truncate(self.max_len);
+
+        self.line = line;
+        self.cursor = self.line.len();
+    }
+}
+
+// Redraws the line being edited, starting at `start_column` of the bottom row.
+fn render(start_column: usize, editor: &LineEditor) {
+    interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+
+        writer.set_column_position(start_column);
+
+        // One screen cell per character, so the cursor column stays in step.
+        for &character in editor.line() {
+            writer.write_glyph(cp437::encode(character));
+        }
+
+        let end = writer.column_position();
+
+        writer.clear_to_end_of_line(end);
+
+        writer.set_column_position(start_column + editor.cursor());
+    });
+}
+
+// Reads lines of keyboard input. It keeps its keyboard subscription between
+// lines, so keys typed while no line is being read (e.g., while the previous
+// one is being handled) aren't lost.
+pub struct LineReader {
+    events: KeyEventStream,
+}
+
+impl LineReader {
+    pub fn new() -> Self {
+        LineReader {
+            events: KeyEventStream::new(),
+        }
+    }
+
+    // Reads one line, echoing it (with line editing) after whatever has been
+    // printed on the current line so far, e.g. a prompt. The returned line
+    // doesn't include the newline.
+    pub async fn read_line(&mut self) -> String {
+        read_line(&mut self.events).await
+    }
+}
+
+impl Default for LineReader {
+    fn default() -> Self {
+        LineReader::new()
+    }
+}
+
+async fn read_line(events: &mut KeyEventStream) -> String {
+    let start_column = interrupts::without_interrupts(|| WRITER.lock().column_position());
+
+    // The line stays on one screen row, so it's never scrolled out from under
+    // us while it's being edited.
+    let max_len = BUFFER_WIDTH.saturating_sub(start_column + 1);
+
+    let history = interrupts::without_interrupts(|| HISTORY.lock().iter().cloned().collect());
+
+    let mut editor = LineEditor::new(max_len, history);
+
+    while let Some(event) = events.next().await {
+        if let Some(line) = editor.handle(&event) {
+            // Leaves the final line on screen, and starts a new one below it.
+            render(start_column, &editor);
+
+            println!();
+
+            remember(&line);
+
+            return line;
+        }
+
+        render(start_column, &editor);
+    }
+
+    // The keyboard service never stops, so we won't get here.
+    String::new()
+}
+
+fn remember(line: &str) {
+    if line.trim().is_empty() {
+        return;
+    }
+
+    interrupts::without_interrupts(|| {
+        let mut history = HISTORY.lock();
+
+        if history.back().map(String::as_str) == Some(line) {
+            return;
+        }
+
+        if history.len() == HISTORY_CAPACITY {
+            history.pop_front();
+        }
+
+        history.push_back(String::from(line));
+    });
+}
+
+#[cfg(test)]
+fn key(code: KeyCode, unicode: Option<char>, ctrl: bool) -> KeyboardEvent {
+    use super::KeyModifiers;
+
+    KeyboardEvent {
+        code,
+        pressed: true,
+        modifiers: KeyModifiers {
+            ctrl,
+            ..KeyModifiers::default()
+        },
+        unicode,
+    }
+}
+
+#[test_case]
+fn test_line_editing() {
+    let mut editor = LineEditor::new(16, Vec::new());
+
+    for (code, character) in [(KeyCode::A, 'a'), (KeyCode::B, 'b'), (KeyCode::Spacebar, ' ')] {
+        editor.handle(&key(code, Some(character), false));
+    }
+
+    editor.handle(&key(KeyCode::C, Some('c'), false));
+    editor.handle(&key(KeyCode::Home, None, false));
+    editor.handle(&key(KeyCode::ArrowRight, None, false));
+    editor.handle(&key(KeyCode::Delete, Some('\u{7f}'), false));
+
+    assert_eq!(editor.line(), ['a', ' ', 'c']);
+
+    editor.handle(&key(KeyCode::End, None, false));
+    editor.handle(&key(KeyCode::W, Some('w'), true));
+
+    assert_eq!(editor.line(), ['a', ' ']);
+
+    editor.handle(&key(KeyCode::U, Some('u'), true));
+
+    assert!(editor.line().is_empty());
+
+    editor.handle(&key(KeyCode::Z, Some('z'), false));
+
+    let line = editor.handle(&key(KeyCode::Return, Some('\n'), false));
+
+    assert_eq!(line.as_deref(), Some("z"));
+}
+
+#[test_case]
+fn test_history_browsing() {
+    let history = ["first", "second"].map(String::from).to_vec();
+
+    let mut editor = LineEditor::new(16, history);
+
+    editor.handle(&key(KeyCode::X, Some('x'), false));
+    editor.handle(&key(KeyCode::ArrowUp, None, false));
+    editor.handle(&key(KeyCode::ArrowUp, None, false));
+
+    assert_eq!(editor.line().iter().collect::<String>(), "first");
+
+    editor.handle(&key(KeyCode::ArrowDown, None, false));
+    editor.handle(&key(KeyCode::ArrowDown, None, false));
+
+    assert_eq!(editor.line(), ['x']);
+}
@@ -1,5 +1,6 @@
 use core::{
     pin::Pin,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
     task::{Context, Poll},
 };
 
@@ -12,9 +13,20 @@ use conquer_once::spin::OnceCell;
 
 use crossbeam_queue::ArrayQueue;
 
-use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+use crate::{
+    interrupts::deferred::{self, Work},
+    log, print,
+};
+
+pub use self::{
+    event::{keyboard_task, KeyEventStream, KeyModifiers, KeyboardEvent},
+    line::LineReader,
+};
 
-use crate::{print, println};
+mod event;
+pub mod hotkey;
+pub mod layout;
+pub mod line;
 
 static WAKER: AtomicWaker = AtomicWaker::new();
 
@@ -24,10 +36,94 @@ static WAKER: AtomicWaker = AtomicWaker::new();
 // require a lock on our heap allocator, which may cause deadlocks.
 static SCANCODE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
 
+const DEFAULT_QUEUE_CAPACITY: usize = 100;
+
+// The capacity SCANCODE_QUEUE will be created with.
+static QUEUE_CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_QUEUE_CAPACITY);
+
+// Scancodes lost because the queue was full, or not yet created.
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+static UNINITIALIZED: AtomicU64 = AtomicU64::new(0);
+
+// The totals as of the last report; only `report_losses()` touches these.
+static REPORTED_DROPPED: AtomicU64 = AtomicU64::new(0);
+static REPORTED_UNINITIALIZED: AtomicU64 = AtomicU64::new(0);
+
+// Set while a report is waiting to run, so that a flood of lost scancodes
+// schedules only one.
+static REPORT_SCHEDULED: AtomicBool = AtomicBool::new(false);
+
+// Sets the capacity of the scancode queue. Takes effect when the queue is
+// created (by `ScancodeStream::new()`), so must be called before then.
+pub fn set_queue_capacity(capacity: usize) {
+    assert!(capacity > 0, "Scancode queue capacity must be non-zero.");
+
+    assert!(
+        SCANCODE_QUEUE.try_get().is_err(),
+        "The scancode queue has already been created."
+    );
+
+    QUEUE_CAPACITY.store(capacity, Ordering::Relaxed);
+}
+
+// Scancodes dropped because the queue was full.
+pub fn dropped_scancodes() -> u64 {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+// Scancodes dropped because they arrived before the queue was created.
+pub fn uninitialized_scancodes() -> u64 {
+    UNINITIALIZED.load(Ordering::Relaxed)
+}
+
+// Printing takes the WRITER lock, so the interrupt handler only counts lost
+// scancodes; they're reported later, from task context.
+fn schedule_report() {
+    if REPORT_SCHEDULED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    // If the work can't be queued either, the next lost scancode tries again.
+    if !deferred::schedule(Work::new(report_losses, 0)) {
+        REPORT_SCHEDULED.store(false, Ordering::Release);
+    }
+}
+
+// Reports the scancodes lost since the last report.
+fn report_losses(_: u64) {
+    // Cleared first, so that losses counted from here on schedule another
+    // report, rather than going unreported.
+    REPORT_SCHEDULED.store(false, Ordering::Release);
+
+    let dropped = DROPPED.load(Ordering::Relaxed);
+    let reported = REPORTED_DROPPED.swap(dropped, Ordering::Relaxed);
+
+    if dropped > reported {
+        log!(
+            "WARNING: scancode queue is full! Dropped {} scancodes ({} in total).",
+            dropped - reported,
+            dropped
+        );
+    }
+
+    let uninitialized = UNINITIALIZED.load(Ordering::Relaxed);
+    let reported = REPORTED_UNINITIALIZED.swap(uninitialized, Ordering::Relaxed);
+
+    if uninitialized > reported {
+        log!(
+            "WARNING: scancode queue uninitialized! Dropped {} scancodes ({} in total).",
+            uninitialized - reported,
+            uninitialized
+        );
+    }
+}
+
 pub(crate) fn add_scancode(scancode: u8) {
     if let Ok(queue) = SCANCODE_QUEUE.try_get() {
         if let Err(_) = queue.push(scancode) {
-            println!("WARNING: scancode queue is full! Dropping keyboard input.");
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+
+            schedule_report();
         } else {
             // Calls `wake()` on the last Waker passed to
             // `AtomicWaker::register()`; this notifies our executor. If no
@@ -36,10 +132,15 @@ pub(crate) fn add_scancode(scancode: u8) {
             WAKER.wake();
         }
     } else {
-        println!("WARNING: scancode queue uninitialized!");
+        UNINITIALIZED.fetch_add(1, Ordering::Relaxed);
+
+        schedule_report();
     }
 }
 
+// The raw scancodes received from the keyboard. Only the keyboard service
+// (`keyboard_task()`) reads these; everything else should subscribe to its
+// decoded events through a `KeyEventStream`.
 pub struct ScancodeStream {
     // Prevents external modules from trying to make a ScancodeStream without
     // using the ScancodeStream::new() path.
@@ -47,9 +148,10 @@ pub struct ScancodeStream {
 }
 
 impl ScancodeStream {
-    pub fn new() -> Self {
+    // Only the keyboard service may create one, and only once.
+    pub(super) fn new() -> Self {
         SCANCODE_QUEUE
-            .try_init_once(|| ArrayQueue::new(100))
+            .try_init_once(|| ArrayQueue::new(QUEUE_CAPACITY.load(Ordering::Relaxed)))
             .expect("ScancodeStream::new() should only be called once!");
 
         ScancodeStream { _private: () }
@@ -90,31 +192,49 @@ impl Stream for ScancodeStream {
     }
 }
 
+// Prints each key typed, e.g. as a demonstration of `KeyEventStream`.
 pub async fn print_keypresses_task() {
-    let mut scancodes = ScancodeStream::new();
+    let mut events = KeyEventStream::new();
 
-    let mut keyboard = Keyboard::new(
-        ScancodeSet1::new(),
-        layouts::Us104Key,
-        HandleControl::Ignore,
-    );
+    // Since the keyboard service never stops, neither does this task.
+    while let Some(event) = events.next().await {
+        if !event.pressed || event.is_modifier() {
+            continue;
+        }
 
-    // Asynchronously wait for the result of each Future returned by
-    // ScancodeStream::next(). Since ScancodeStream::next() never returns None,
-    // this means that our print_keypresses() task runs indefinitely.
+        match event.unicode {
+            Some(character) => print!("{}", character),
+            None => print!("{:?}", event.code),
+        }
+    }
+}
 
-    while let Some(scancode) = scancodes.next().await {
-        // Processes an 8-bit scancode into a keyboard event.
+#[test_case]
+fn test_flooding_the_queue_counts_dropped_scancodes() {
+    use futures_util::task::noop_waker_ref;
 
-        if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
-            // Produces a decoded key from the key event.
+    set_queue_capacity(8);
 
-            if let Some(key) = keyboard.process_keyevent(key_event) {
-                match key {
-                    DecodedKey::Unicode(character) => print!("{}", character),
-                    DecodedKey::RawKey(key) => print!("{:?}", key),
-                }
-            }
-        }
+    let mut stream = ScancodeStream::new();
+
+    let dropped = dropped_scancodes();
+
+    for scancode in 0..20 {
+        add_scancode(scancode);
     }
+
+    assert_eq!(dropped_scancodes() - dropped, 12);
+
+    // The queue kept the oldest scancodes.
+    let mut cx = Context::from_waker(noop_waker_ref());
+
+    for scancode in 0..8 {
+        assert_eq!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Ready(Some(scancode)));
+    }
+
+    assert_eq!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Pending);
+
+    report_losses(0);
+
+    assert_eq!(REPORTED_DROPPED.load(Ordering::Relaxed), dropped_scancodes());
 }
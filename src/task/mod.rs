@@ -1,17 +1,23 @@
 use core::{
     future::Future,
     pin::Pin,
-    sync::atomic::{AtomicU64, Ordering},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
     task::{Context, Poll},
 };
 
 extern crate alloc;
 
-use alloc::boxed::Box;
+use alloc::{boxed::Box, sync::Arc};
 
+pub mod channel;
+pub mod combinator;
 pub mod executor;
+pub mod join;
 pub mod keyboard;
+pub mod mouse;
+pub mod scope;
 pub mod simple_executor;
+pub mod sync;
 
 // A type wrapper around a pinned, heap-allocated, dynamically dispatched
 // future, whose output type is an Empty. A task is thus executed for its
@@ -19,27 +25,141 @@ pub mod simple_executor;
 pub struct Task {
     id: TaskId,
 
+    // Shown in task listings (see `executor::Snapshot`).
+    name: &'static str,
+
     // The Box holds a dynamically dispatched trait object, so that
-    // different tasks can use different types of Futures.
-    future: Pin<Box<dyn Future<Output = ()>>>,
+    // different tasks can use different types of Futures. Futures must be
+    // `Send`, so that tasks can be handed to the executor through a `Spawner`
+    // from any context.
+    future: Pin<Box<dyn Future<Output = ()> + Send>>,
+
+    // Shared with the task's waker and the executor's task registry.
+    stats: Arc<TaskStats>,
+
+    priority: Priority,
+}
+
+// Scheduling classes, from most to least urgent. The executor keeps a separate
+// ready queue for each, and services them in proportion to their weights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Priority {
+    // Work deferred by interrupt handlers.
+    InterruptDeferred = 0,
+    // Tasks a user is waiting on (e.g., keyboard handling).
+    Interactive = 1,
+    Normal = 2,
+    // Bulk work that should only soak up otherwise idle time.
+    Background = 3,
+}
+
+impl Priority {
+    pub const COUNT: usize = 4;
+
+    pub const ALL: [Priority; Priority::COUNT] = [
+        Priority::InterruptDeferred,
+        Priority::Interactive,
+        Priority::Normal,
+        Priority::Background,
+    ];
+
+    // Relative share of polls each class receives while all are busy.
+    fn weight(self) -> u32 {
+        match self {
+            Priority::InterruptDeferred => 8,
+            Priority::Interactive => 4,
+            Priority::Normal => 2,
+            Priority::Background => 1,
+        }
+    }
 }
 
 impl Task {
     // The returned Task instance may have an arbitrary lifetime, so the
     // lifetime of the surrounding Box (wrapper) must outlive it.
-    pub fn new(future: impl Future<Output = ()> + 'static) -> Task {
+    pub fn new(future: impl Future<Output = ()> + Send + 'static) -> Task {
+        Task::with_priority(future, Priority::Normal)
+    }
+
+    pub fn with_priority<F>(future: F, priority: Priority) -> Task
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
         Task {
             id: TaskId::new(),
+            name: default_name::<F>(),
             future: Box::pin(future),
+            stats: Arc::new(TaskStats::new()),
+            priority,
         }
     }
 
+    // Replaces the task's name, which otherwise defaults to the name of the
+    // function that created its future.
+    pub fn named(mut self, name: &'static str) -> Task {
+        self.name = name;
+
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
     // Polls the Future, providing the given Context.
     fn poll(&mut self, context: &mut Context) -> Poll<()> {
         self.future.as_mut().poll(context)
     }
 }
 
+// Derives a readable name from a future's type; for an `async fn`, that's
+// e.g. "rust_os::task::keyboard::print_keypresses_task::{{closure}}", which
+// we shorten to "print_keypresses_task".
+fn default_name<F>() -> &'static str {
+    let name = core::any::type_name::<F>();
+
+    let name = name.strip_suffix("::{{closure}}").unwrap_or(name);
+
+    // Generic types would be cut mid-way, so those keep their full name.
+    if name.contains('<') {
+        return name;
+    }
+
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+// Bookkeeping shared between a task, its waker and the executor's task
+// registry. Everything is atomic, since wakers may run in interrupt handlers.
+struct TaskStats {
+    // Whether the task is currently in the executor's ready queue, so that a
+    // task is queued at most once at a time.
+    scheduled: AtomicBool,
+
+    poll_count: AtomicU64,
+
+    // Total time spent inside the task's `poll()`, in TSC cycles.
+    poll_cycles: AtomicU64,
+
+    // TSC value at the most recent wakeup, or 0 if never woken.
+    last_woken: AtomicU64,
+}
+
+impl TaskStats {
+    fn new() -> Self {
+        TaskStats {
+            scheduled: AtomicBool::new(false),
+            poll_count: AtomicU64::new(0),
+            poll_cycles: AtomicU64::new(0),
+            last_woken: AtomicU64::new(0),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 struct TaskId(u64);
 
@@ -0,0 +1,198 @@
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use conquer_once::spin::OnceCell;
+
+use crossbeam_queue::ArrayQueue;
+
+use futures_util::{stream::Stream, task::AtomicWaker};
+
+use crate::ps2;
+
+// Room for a few dozen packets.
+const QUEUE_CAPACITY: usize = 128;
+
+// Bits of a packet's first byte.
+const LEFT_BUTTON: u8 = 1 << 0;
+const RIGHT_BUTTON: u8 = 1 << 1;
+const MIDDLE_BUTTON: u8 = 1 << 2;
+const ALWAYS_ONE: u8 = 1 << 3;
+const X_SIGN: u8 = 1 << 4;
+const Y_SIGN: u8 = 1 << 5;
+const X_OVERFLOW: u8 = 1 << 6;
+const Y_OVERFLOW: u8 = 1 << 7;
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+// Raw packet bytes from the mouse interrupt handler. Like the scancode queue,
+// this is initialized outside of the handler, so that the handler never
+// allocates.
+static BYTE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MouseButtons {
+    pub left: bool,
+    pub right: bool,
+    pub middle: bool,
+}
+
+// One packet's worth of mouse input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    // Movement since the previous event. As is the PS/2 convention, positive
+    // `dy` is upwards.
+    pub dx: i16,
+    pub dy: i16,
+    // Scroll wheel clicks since the previous event; positive is towards the
+    // user. Always 0 without a scroll wheel.
+    pub wheel: i8,
+    // The buttons held down as of this event.
+    pub buttons: MouseButtons,
+}
+
+pub(crate) fn add_byte(byte: u8) {
+    // Until a `MouseStream` exists, no one is listening, so there's nowhere
+    // for the input to go. If the queue is full, the byte is dropped, and the
+    // stream resynchronizes with the packets that follow.
+    if let Ok(queue) = BYTE_QUEUE.try_get() {
+        if queue.push(byte).is_ok() {
+            WAKER.wake();
+        }
+    }
+}
+
+// Decodes a complete (3- or 4-byte) packet. Packets whose movement overflowed
+// are discarded, since their movement is meaningless.
+fn decode(packet: &[u8]) -> Option<MouseEvent> {
+    let flags = packet[0];
+
+    if flags & (X_OVERFLOW | Y_OVERFLOW) != 0 {
+        return None;
+    }
+
+    // Movement is a 9-bit two's complement value, whose sign bit is in the
+    // first byte.
+    let dx = packet[1] as i16 - if flags & X_SIGN != 0 { 256 } else { 0 };
+    let dy = packet[2] as i16 - if flags & Y_SIGN != 0 { 256 } else { 0 };
+
+    // The wheel's movement is a 4-bit two's complement value.
+    let wheel = match packet.get(3) {
+        Some(&byte) => ((byte << 4) as i8) >> 4,
+        None => 0,
+    };
+
+    Some(MouseEvent {
+        dx,
+        dy,
+        wheel,
+        buttons: MouseButtons {
+            left: flags & LEFT_BUTTON != 0,
+            right: flags & RIGHT_BUTTON != 0,
+            middle: flags & MIDDLE_BUTTON != 0,
+        },
+    })
+}
+
+// The events from the PS/2 mouse (see `ps2::init_mouse()`).
+pub struct MouseStream {
+    // The packet received so far.
+    packet: [u8; 4],
+    received: usize,
+
+    packet_size: usize,
+}
+
+impl MouseStream {
+    pub fn new() -> Self {
+        BYTE_QUEUE
+            .try_init_once(|| ArrayQueue::new(QUEUE_CAPACITY))
+            .expect("MouseStream::new() should only be called once!");
+
+        MouseStream {
+            packet: [0; 4],
+            received: 0,
+            packet_size: ps2::mouse_packet_size().unwrap_or(3),
+        }
+    }
+
+    // Adds a byte to the current packet, returning its event once it's
+    // complete.
+    fn add_byte(&mut self, byte: u8) -> Option<MouseEvent> {
+        // A packet's first byte always has this bit set; skipping bytes
+        // without it lets us find the start of the next packet after losing
+        // our place (e.g., because bytes were dropped).
+        if self.received == 0 && byte & ALWAYS_ONE == 0 {
+            return None;
+        }
+
+        self.packet[self.received] = byte;
+        self.received += 1;
+
+        if self.received < self.packet_size {
+            return None;
+        }
+
+        self.received = 0;
+
+        decode(&self.packet[..self.packet_size])
+    }
+}
+
+impl Default for MouseStream {
+    fn default() -> Self {
+        MouseStream::new()
+    }
+}
+
+impl Stream for MouseStream {
+    type Item = MouseEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<MouseEvent>> {
+        let stream = self.get_mut();
+
+        let queue = BYTE_QUEUE.try_get().expect("mouse queue uninitialized!");
+
+        loop {
+            let byte = match queue.pop() {
+                Ok(byte) => byte,
+                Err(_) => {
+                    // As with `ScancodeStream`, the interrupt handler may push
+                    // a byte between our `pop()` and registering our Waker, so
+                    // we check again afterwards.
+                    WAKER.register(cx.waker());
+
+                    match queue.pop() {
+                        Ok(byte) => {
+                            WAKER.take();
+
+                            byte
+                        }
+                        Err(crossbeam_queue::PopError) => return Poll::Pending,
+                    }
+                }
+            };
+
+            if let Some(event) = stream.add_byte(byte) {
+                return Poll::Ready(Some(event));
+            }
+        }
+    }
+}
+
+#[test_case]
+fn test_decode_packets() {
+    // Left button held, moving left and up.
+    let event = decode(&[ALWAYS_ONE | LEFT_BUTTON | X_SIGN, 0xfe, 0x03]).unwrap();
+
+    assert_eq!((event.dx, event.dy, event.wheel), (-2, 3, 0));
+    assert!(event.buttons.left && !event.buttons.right);
+
+    // One wheel click away from the user.
+    let event = decode(&[ALWAYS_ONE, 0, 0, 0x0f]).unwrap();
+
+    assert_eq!(event.wheel, -1);
+
+    assert_eq!(decode(&[ALWAYS_ONE | X_OVERFLOW, 0xff, 0]), None);
+}
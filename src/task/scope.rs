@@ -0,0 +1,166 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+
+extern crate alloc;
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+
+use futures_util::future::BoxFuture;
+
+use spin::Mutex;
+
+use x86_64::instructions::interrupts;
+
+use super::{
+    default_name,
+    executor::Spawner,
+    join::{joinable, AbortHandle, JoinHandle},
+    sync::Notify,
+};
+
+// Structured concurrency: tasks spawned through a `Scope` can't outlive it.
+// The future returned by `scope()` only resolves once every child task has
+// finished, and dropping that future (i.e., cancelling it) aborts them all.
+//
+//     let total = scope(spawner, |scope| {
+//         Box::pin(async move {
+//             let a = scope.spawn(count_a());
+//             let b = scope.spawn(count_b());
+//
+//             a.await.unwrap() + b.await.unwrap()
+//         })
+//     })
+//     .await;
+//
+// The body only ever borrows its `Scope`, so it can't smuggle the scope out
+// and spawn children after `scope()` has resolved.
+pub struct Scope {
+    spawner: Spawner,
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    // Number of child tasks whose futures haven't been dropped yet.
+    active: AtomicUsize,
+
+    // Notified whenever `active` drops to zero.
+    idle: Notify,
+
+    // For cancelling children that are still running.
+    children: Mutex<Vec<AbortHandle>>,
+}
+
+impl Inner {
+    fn cancel(&self) {
+        interrupts::without_interrupts(|| {
+            for child in self.children.lock().iter() {
+                child.abort();
+            }
+        });
+    }
+}
+
+impl Scope {
+    // Spawns `future` as a child task of this scope.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.inner.active.fetch_add(1, Ordering::AcqRel);
+
+        let (task, handle) = joinable(Child {
+            future: Some(Box::pin(future)),
+            scope: self.inner.clone(),
+        });
+
+        // Named after the child's future, rather than after `Child`.
+        self.spawner.spawn(task.named(default_name::<F>()));
+
+        interrupts::without_interrupts(|| {
+            let mut children = self.inner.children.lock();
+
+            // Keeps the list from growing with children that are long gone.
+            children.retain(|child| !child.is_finished());
+
+            children.push(handle.abort_handle());
+        });
+
+        handle
+    }
+
+    // Aborts every child task that's still running; `scope()` still waits for
+    // them to be torn down.
+    pub fn cancel(&self) {
+        self.inner.cancel();
+    }
+}
+
+// Runs `body` with a new scope, resolving to its output once `body` and every
+// child task spawned in the scope have finished. The body's future is boxed,
+// since it borrows the scope.
+pub async fn scope<F, T>(spawner: Spawner, body: F) -> T
+where
+    F: for<'a> FnOnce(&'a Scope) -> BoxFuture<'a, T>,
+{
+    let scope = Scope {
+        spawner,
+        inner: Arc::new(Inner {
+            active: AtomicUsize::new(0),
+            idle: Notify::new(),
+            children: Mutex::new(Vec::new()),
+        }),
+    };
+
+    // Aborts the children if we're dropped before they've finished.
+    let _guard = CancelOnDrop(scope.inner.clone());
+
+    let output = body(&scope).await;
+
+    // A `notify_one()` with nobody waiting leaves a permit, so we can't miss
+    // the last child's notification.
+    while scope.inner.active.load(Ordering::Acquire) > 0 {
+        scope.inner.idle.notified().await;
+    }
+
+    output
+}
+
+struct CancelOnDrop(Arc<Inner>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+// Wraps a child task's future, to count it as active until it's dropped
+// (whether it completed or was cancelled).
+struct Child<F> {
+    // Only `None` while the child is being dropped.
+    future: Option<Pin<Box<F>>>,
+    scope: Arc<Inner>,
+}
+
+impl<F: Future> Future for Child<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        self.future.as_mut().unwrap().as_mut().poll(cx)
+    }
+}
+
+impl<F> Drop for Child<F> {
+    fn drop(&mut self) {
+        // The future must be gone before the scope can resolve.
+        drop(self.future.take());
+
+        if self.scope.active.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.scope.idle.notify_one();
+        }
+    }
+}
@@ -0,0 +1,15 @@
+// Executor-friendly synchronization primitives. Unlike `spin::Mutex`, these
+// park a waiting task (via its `Waker`) instead of busy-waiting, so they may
+// be held across `.await` points without deadlocking the executor.
+
+pub mod mutex;
+pub mod notify;
+pub mod rwlock;
+pub mod semaphore;
+
+pub use self::{
+    mutex::{Mutex, MutexGuard},
+    notify::Notify,
+    rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+    semaphore::{Semaphore, SemaphorePermit},
+};
@@ -0,0 +1,72 @@
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+};
+
+use super::semaphore::Semaphore;
+
+// An async mutual-exclusion lock. Unlike `spin::Mutex`, waiting for it parks
+// the task (rather than spinning), so its guard can be held across `.await`
+// points. Waiters acquire the lock in FIFO order.
+pub struct Mutex<T> {
+    semaphore: Semaphore,
+    data: UnsafeCell<T>,
+}
+
+// Safety: access to `data` is serialized by the (single-permit) semaphore.
+unsafe impl<T: Send> Send for Mutex<T> {}
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    pub const fn new(data: T) -> Self {
+        Mutex {
+            semaphore: Semaphore::new(1),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        // The guard returns the permit itself, when it's dropped.
+        self.semaphore.acquire().await.forget();
+
+        MutexGuard { mutex: self }
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.semaphore.try_acquire().map(|permit| {
+            permit.forget();
+
+            MutexGuard { mutex: self }
+        })
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+}
+
+unsafe impl<T: Sync> Sync for MutexGuard<'_, T> {}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.semaphore.add_permits(1);
+    }
+}
@@ -0,0 +1,202 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+extern crate alloc;
+
+use alloc::collections::VecDeque;
+
+use spin::Mutex;
+
+use x86_64::instructions::interrupts;
+
+// Wakes one or all waiting tasks, without transferring any data. The notify
+// side never allocates and only holds its lock with interrupts disabled, so
+// `notify_one()` and `notify_waiters()` may be called from interrupt handlers.
+pub struct Notify {
+    state: Mutex<State>,
+}
+
+struct State {
+    // A `notify_one()` with nobody waiting is stored, and consumed by the next
+    // call to `notified()`.
+    permit: bool,
+    waiters: VecDeque<Waiter>,
+    next_waiter_id: u64,
+}
+
+struct Waiter {
+    id: u64,
+    waker: Waker,
+
+    // Set when this waiter is notified; it leaves the queue when its
+    // `Notified` future next observes this.
+    notification: Option<Notification>,
+}
+
+#[derive(Clone, Copy)]
+enum Notification {
+    One,
+    All,
+}
+
+impl Notify {
+    pub const fn new() -> Self {
+        Notify {
+            state: Mutex::new(State {
+                permit: false,
+                waiters: VecDeque::new(),
+                next_waiter_id: 0,
+            }),
+        }
+    }
+
+    // Wakes the longest-waiting task, or stores a permit if none is waiting.
+    pub fn notify_one(&self) {
+        interrupts::without_interrupts(|| {
+            let mut guard = self.state.lock();
+
+            let state = &mut *guard;
+
+            let waiter = state
+                .waiters
+                .iter_mut()
+                .find(|waiter| waiter.notification.is_none());
+
+            match waiter {
+                Some(waiter) => {
+                    waiter.notification = Some(Notification::One);
+                    waiter.waker.wake_by_ref();
+                }
+                None => state.permit = true,
+            }
+        });
+    }
+
+    // Wakes every task that's currently waiting (but stores no permit).
+    pub fn notify_waiters(&self) {
+        interrupts::without_interrupts(|| {
+            let mut state = self.state.lock();
+
+            let waiters = state
+                .waiters
+                .iter_mut()
+                .filter(|waiter| waiter.notification.is_none());
+
+            for waiter in waiters {
+                waiter.notification = Some(Notification::All);
+                waiter.waker.wake_by_ref();
+            }
+        });
+    }
+
+    // Waits for a notification.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            waiter_id: None,
+            done: false,
+        }
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Notify::new()
+    }
+}
+
+// Future returned by `Notify::notified()`.
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    waiter_id: Option<u64>,
+    done: bool,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let notify = self.notify;
+        let waiter_id = self.waiter_id;
+
+        let (poll, registered_id) = interrupts::without_interrupts(|| {
+            let mut state = notify.state.lock();
+
+            let id = match waiter_id {
+                Some(id) => id,
+                None => {
+                    if state.permit {
+                        state.permit = false;
+
+                        return (Poll::Ready(()), None);
+                    }
+
+                    let id = state.next_waiter_id;
+
+                    state.next_waiter_id += 1;
+
+                    // Registration may allocate, but only ever happens in task
+                    // context.
+                    state.waiters.push_back(Waiter {
+                        id,
+                        waker: cx.waker().clone(),
+                        notification: None,
+                    });
+
+                    return (Poll::Pending, Some(id));
+                }
+            };
+
+            let index = state
+                .waiters
+                .iter()
+                .position(|waiter| waiter.id == id)
+                .expect("Notify waiter went missing.");
+
+            if state.waiters[index].notification.is_some() {
+                state.waiters.remove(index);
+
+                (Poll::Ready(()), None)
+            } else {
+                state.waiters[index].waker = cx.waker().clone();
+
+                (Poll::Pending, None)
+            }
+        });
+
+        if registered_id.is_some() {
+            self.waiter_id = registered_id;
+        }
+
+        if poll.is_ready() {
+            self.done = true;
+        }
+
+        poll
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        let id = match (self.waiter_id, self.done) {
+            (Some(id), false) => id,
+            _ => return,
+        };
+
+        let forward = interrupts::without_interrupts(|| {
+            let mut state = self.notify.state.lock();
+
+            let index = state.waiters.iter().position(|waiter| waiter.id == id)?;
+
+            state.waiters.remove(index)?.notification
+        });
+
+        // A `notify_one()` aimed at us mustn't be lost; passes it along.
+        if let Some(Notification::One) = forward {
+            self.notify.notify_one();
+        }
+    }
+}
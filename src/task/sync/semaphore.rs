@@ -0,0 +1,234 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+extern crate alloc;
+
+use alloc::collections::VecDeque;
+
+use spin::Mutex;
+
+use x86_64::instructions::interrupts;
+
+// An async counting semaphore. Waiters are served strictly in FIFO order: a
+// request that can't be satisfied yet blocks every request queued behind it,
+// so large requests (e.g., `RwLock` writers) can't be starved.
+pub struct Semaphore {
+    state: Mutex<State>,
+}
+
+struct State {
+    permits: usize,
+    waiters: VecDeque<Waiter>,
+    next_waiter_id: u64,
+}
+
+struct Waiter {
+    id: u64,
+    permits: usize,
+    waker: Waker,
+
+    // Set once `permits` have been assigned to this waiter; it leaves the
+    // queue when its `Acquire` future next observes this.
+    granted: bool,
+}
+
+impl State {
+    // Assigns permits to waiters at the front of the queue, for as long as
+    // there are enough permits to do so.
+    fn grant_waiters(&mut self) {
+        for waiter in self.waiters.iter_mut().filter(|waiter| !waiter.granted) {
+            if waiter.permits > self.permits {
+                break;
+            }
+
+            self.permits -= waiter.permits;
+
+            waiter.granted = true;
+            waiter.waker.wake_by_ref();
+        }
+    }
+
+    fn has_ungranted_waiters(&self) -> bool {
+        self.waiters.iter().any(|waiter| !waiter.granted)
+    }
+}
+
+impl Semaphore {
+    pub const fn new(permits: usize) -> Self {
+        Semaphore {
+            state: Mutex::new(State {
+                permits,
+                waiters: VecDeque::new(),
+                next_waiter_id: 0,
+            }),
+        }
+    }
+
+    pub fn available_permits(&self) -> usize {
+        interrupts::without_interrupts(|| self.state.lock().permits)
+    }
+
+    // Waits until a single permit is available, and takes it.
+    pub fn acquire(&self) -> Acquire<'_> {
+        self.acquire_many(1)
+    }
+
+    // Waits until `permits` permits are available, and takes them.
+    pub fn acquire_many(&self, permits: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            permits,
+            waiter_id: None,
+            acquired: false,
+        }
+    }
+
+    // Takes `permits` permits, if they're available right now (and nobody is
+    // already waiting for permits).
+    pub fn try_acquire_many(&self, permits: usize) -> Option<SemaphorePermit<'_>> {
+        interrupts::without_interrupts(|| {
+            let mut state = self.state.lock();
+
+            if state.permits >= permits && !state.has_ungranted_waiters() {
+                state.permits -= permits;
+
+                Some(SemaphorePermit {
+                    semaphore: self,
+                    permits,
+                })
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        self.try_acquire_many(1)
+    }
+
+    // Returns permits to the semaphore, waking waiters that can now proceed.
+    pub fn add_permits(&self, permits: usize) {
+        interrupts::without_interrupts(|| {
+            let mut state = self.state.lock();
+
+            state.permits += permits;
+
+            state.grant_waiters();
+        });
+    }
+}
+
+// Future returned by `Semaphore::acquire()`.
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+    waiter_id: Option<u64>,
+    acquired: bool,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let semaphore = self.semaphore;
+        let permits = self.permits;
+
+        interrupts::without_interrupts(|| {
+            let mut state = semaphore.state.lock();
+
+            match self.waiter_id {
+                None => {
+                    // Fast path: permits are available, and nobody's ahead of us.
+                    if state.permits >= permits && !state.has_ungranted_waiters() {
+                        state.permits -= permits;
+                    } else {
+                        let id = state.next_waiter_id;
+
+                        state.next_waiter_id += 1;
+
+                        state.waiters.push_back(Waiter {
+                            id,
+                            permits,
+                            waker: cx.waker().clone(),
+                            granted: false,
+                        });
+
+                        self.waiter_id = Some(id);
+
+                        return Poll::Pending;
+                    }
+                }
+                Some(id) => {
+                    let index = state
+                        .waiters
+                        .iter()
+                        .position(|waiter| waiter.id == id)
+                        .expect("Semaphore waiter went missing.");
+
+                    if !state.waiters[index].granted {
+                        state.waiters[index].waker = cx.waker().clone();
+
+                        return Poll::Pending;
+                    }
+
+                    state.waiters.remove(index);
+                }
+            }
+
+            self.acquired = true;
+
+            Poll::Ready(SemaphorePermit { semaphore, permits })
+        })
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        // Only a future that's queued (and didn't complete) needs cleaning up.
+        let id = match (self.waiter_id, self.acquired) {
+            (Some(id), false) => id,
+            _ => return,
+        };
+
+        interrupts::without_interrupts(|| {
+            let mut state = self.semaphore.state.lock();
+
+            if let Some(index) = state.waiters.iter().position(|waiter| waiter.id == id) {
+                let waiter = state.waiters.remove(index).unwrap();
+
+                // Hands back any permits we were granted but never observed.
+                if waiter.granted {
+                    state.permits += waiter.permits;
+                }
+
+                // Our departure may have unblocked the waiters behind us.
+                state.grant_waiters();
+            }
+        });
+    }
+}
+
+// Permits held from a `Semaphore`; they're returned when this is dropped.
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+impl SemaphorePermit<'_> {
+    // Keeps the permits out of the semaphore for good (or until they're
+    // explicitly returned via `Semaphore::add_permits()`).
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+extern crate alloc;
+
+use alloc::{boxed::Box, sync::Arc};
+
+use spin::Mutex;
+
+use x86_64::instructions::interrupts;
+
+use crate::time;
+
+use self::scheduler::{ThreadState, SCHEDULER};
+
+mod context;
+mod scheduler;
+
+pub(crate) use self::scheduler::on_timer_tick;
+
+// Preemptive kernel threads, each with its own stack, scheduled round-robin
+// and preempted by the timer interrupt. The code that calls `init()` becomes
+// the "boot" thread (e.g., the async executor can keep running there).
+//
+// Note that a thread may be preempted at any point where interrupts are
+// enabled; any lock shared between threads must only be held with interrupts
+// disabled (as `println!()` and the heap allocator do), or a preempted holder
+// could leave another thread spinning forever.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ThreadId(u64);
+
+impl ThreadId {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        ThreadId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+// Sets up the scheduler, adopting the caller as the boot thread. Must be
+// called after the heap is initialized, and before any other function here.
+pub fn init() {
+    scheduler::init();
+}
+
+// A handle to a spawned thread. Dropping it detaches the thread, which then
+// runs to completion on its own.
+pub struct JoinHandle<T> {
+    id: ThreadId,
+
+    // Filled in by the thread just before it finishes.
+    result: Arc<Mutex<Option<T>>>,
+
+    joined: bool,
+}
+
+impl<T> JoinHandle<T> {
+    pub fn id(&self) -> ThreadId {
+        self.id
+    }
+
+    // Blocks the calling thread until this thread finishes, returning the
+    // output of its closure.
+    pub fn join(mut self) -> T {
+        scheduler::join(self.id);
+
+        self.joined = true;
+
+        let result = interrupts::without_interrupts(|| self.result.lock().take());
+
+        result.expect("Thread finished without producing a result.")
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        if !self.joined {
+            scheduler::detach(self.id);
+        }
+    }
+}
+
+// Spawns a new thread running `f`; it becomes ready immediately, and first
+// runs when the scheduler next switches threads.
+pub fn spawn<F, T>(name: &'static str, f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let result = Arc::new(Mutex::new(None));
+
+    let thread_result = result.clone();
+
+    let id = scheduler::spawn(
+        name,
+        Box::new(move || {
+            let output = f();
+
+            interrupts::without_interrupts(|| *thread_result.lock() = Some(output));
+        }),
+    );
+
+    JoinHandle {
+        id,
+        result,
+        joined: false,
+    }
+}
+
+// Gives up the rest of the current thread's time slice, if any other thread is
+// ready to run.
+pub fn yield_now() {
+    interrupts::without_interrupts(|| {
+        let guard = SCHEDULER.lock();
+
+        if guard.is_some() {
+            scheduler::switch_to_next(guard);
+        }
+    });
+}
+
+// Blocks the current thread for at least `duration`, rounded up to a whole
+// number of timer ticks.
+pub fn sleep(duration: Duration) {
+    let ticks = (duration.as_nanos() * time::TICKS_PER_SECOND as u128).div_ceil(1_000_000_000);
+
+    if ticks == 0 {
+        return yield_now();
+    }
+
+    // The current tick may be almost over, so we wait for one more.
+    let wake_tick = time::ticks() + ticks as u64 + 1;
+
+    scheduler::block_current(ThreadState::Sleeping(wake_tick));
+}
+
+// Returns the ID of the calling thread.
+pub fn current() -> ThreadId {
+    interrupts::without_interrupts(|| {
+        SCHEDULER
+            .lock()
+            .as_ref()
+            .expect("thread::init() must be called first!")
+            .current
+    })
+}
+
+// Returns the name the calling thread was spawned with.
+pub fn current_name() -> &'static str {
+    interrupts::without_interrupts(|| {
+        let guard = SCHEDULER.lock();
+
+        let scheduler = guard.as_ref().expect("thread::init() must be called first!");
+
+        scheduler.threads[&scheduler.current].name
+    })
+}
@@ -0,0 +1,84 @@
+use core::arch::global_asm;
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+
+// Saves the current thread's callee-saved registers on its stack, stores its
+// stack pointer to `*old_rsp`, then loads `new_rsp` and restores the registers
+// that were saved there (by an earlier switch, or by `initial_stack()`). The
+// x86-64 SysV ABI makes every other register caller-saved, so the compiler
+// has already preserved them around the call.
+global_asm!(
+    ".global rust_os_switch_context",
+    "rust_os_switch_context:",
+    "push rbp",
+    "push rbx",
+    "push r12",
+    "push r13",
+    "push r14",
+    "push r15",
+    "mov [rdi], rsp",
+    "mov rsp, rsi",
+    "pop r15",
+    "pop r14",
+    "pop r13",
+    "pop r12",
+    "pop rbx",
+    "pop rbp",
+    "ret",
+    // A new thread "returns" here from its first switch, with its entry
+    // closure in r12.
+    ".global rust_os_thread_trampoline",
+    "rust_os_thread_trampoline:",
+    "mov rdi, r12",
+    "call {entry}",
+    "ud2",
+    entry = sym super::scheduler::thread_entry,
+);
+
+extern "C" {
+    fn rust_os_switch_context(old_rsp: *mut u64, new_rsp: u64);
+    fn rust_os_thread_trampoline();
+}
+
+// The entry point of a new thread, as passed to `thread_entry()`.
+pub(super) type ThreadStart = Box<dyn FnOnce() + Send + 'static>;
+
+// Switches from the current thread to the thread whose saved stack pointer is
+// `new_rsp`, saving the current thread's stack pointer to `old_rsp`.
+//
+// Unsafe because interrupts must be disabled, and `new_rsp` must have come
+// from an earlier switch or from `initial_stack()`.
+pub(super) unsafe fn switch(old_rsp: *mut u64, new_rsp: u64) {
+    rust_os_switch_context(old_rsp, new_rsp);
+}
+
+// Lays out a new thread's stack so that the first switch to it "returns" into
+// the trampoline, which calls `thread_entry(start)`. Returns the initial
+// stack pointer.
+pub(super) fn initial_stack(stack: &mut [u8], start: *mut ThreadStart) -> u64 {
+    let stack_top = (stack.as_mut_ptr() as u64 + stack.len() as u64) & !0xf;
+
+    // Popped in order by `rust_os_switch_context`: r15, r14, r13, r12, rbx,
+    // rbp, and finally the return address.
+    let frame: [u64; 7] = [
+        0,
+        0,
+        0,
+        start as u64,
+        0,
+        0,
+        rust_os_thread_trampoline as *const () as u64,
+    ];
+
+    let rsp = stack_top - (frame.len() * 8) as u64;
+
+    unsafe {
+        (rsp as *mut [u64; 7]).write(frame);
+    }
+
+    // After the final `ret`, the stack pointer is 16-byte aligned, as the ABI
+    // requires before the trampoline's `call`.
+    rsp
+}
@@ -0,0 +1,357 @@
+extern crate alloc;
+
+use alloc::{boxed::Box, collections::BTreeMap, collections::VecDeque, vec};
+
+use spin::{Mutex, MutexGuard};
+
+use x86_64::instructions::interrupts;
+
+use super::{
+    context::{self, ThreadStart},
+    ThreadId,
+};
+
+// Size of each spawned thread's stack. There's no guard page, so a thread
+// that overflows its stack will silently corrupt the heap.
+const STACK_SIZE: usize = 4096 * 4;
+
+// Number of timer ticks a thread may run before it's preempted.
+const TIME_SLICE_TICKS: u64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ThreadState {
+    Running,
+    Ready,
+    // Asleep until the given timer tick.
+    Sleeping(u64),
+    // Waiting for another thread to finish.
+    Joining,
+    Finished,
+}
+
+pub(super) struct Thread {
+    pub(super) name: &'static str,
+    pub(super) state: ThreadState,
+
+    // Stack pointer saved by the last switch away from this thread.
+    saved_rsp: u64,
+
+    // `None` for the boot thread, which runs on the bootloader's stack.
+    _stack: Option<Box<[u8]>>,
+
+    // The thread (if any) blocked in `join()` on this one.
+    joiner: Option<ThreadId>,
+
+    // Set when the thread's `JoinHandle` is dropped; a detached thread is
+    // cleaned up by the scheduler once it finishes.
+    detached: bool,
+}
+
+pub(super) struct Scheduler {
+    // Boxed, so that each thread's `saved_rsp` has a stable address while we
+    // switch away from it.
+    pub(super) threads: BTreeMap<ThreadId, Box<Thread>>,
+
+    // Threads that are ready to run. Each thread is queued at most once, and
+    // the queue's capacity is kept at least as large as the number of threads,
+    // so that the timer interrupt never makes it allocate.
+    ready: VecDeque<ThreadId>,
+
+    pub(super) current: ThreadId,
+
+    // Runs (halting the CPU) whenever no other thread is ready; it's never
+    // placed in the ready queue.
+    idle: ThreadId,
+
+    slice_remaining: u64,
+}
+
+// Every holder of this lock disables interrupts first, so that the timer
+// interrupt handler can never find it held.
+pub(super) static SCHEDULER: Mutex<Option<Scheduler>> = Mutex::new(None);
+
+pub(super) fn init() {
+    // Created up front, since it's never queued like a spawned thread.
+    let idle = new_thread(
+        "idle",
+        Box::new(|| loop {
+            x86_64::instructions::hlt();
+        }),
+    );
+
+    interrupts::without_interrupts(|| {
+        let mut scheduler = SCHEDULER.lock();
+
+        assert!(scheduler.is_none(), "thread::init() should only be called once!");
+
+        // Adopts the code that's running now as the boot thread.
+        let boot = Box::new(Thread {
+            name: "boot",
+            state: ThreadState::Running,
+            saved_rsp: 0,
+            _stack: None,
+            joiner: None,
+            detached: true,
+        });
+
+        let boot_id = ThreadId::new();
+        let idle_id = ThreadId::new();
+
+        let mut threads = BTreeMap::new();
+
+        threads.insert(boot_id, boot);
+        threads.insert(idle_id, idle);
+
+        *scheduler = Some(Scheduler {
+            threads,
+            ready: VecDeque::with_capacity(2),
+            current: boot_id,
+            idle: idle_id,
+            slice_remaining: TIME_SLICE_TICKS,
+        });
+    });
+}
+
+fn new_thread(name: &'static str, start: ThreadStart) -> Box<Thread> {
+    let mut stack = vec![0u8; STACK_SIZE].into_boxed_slice();
+
+    let start = Box::into_raw(Box::new(start));
+
+    let saved_rsp = context::initial_stack(&mut stack, start);
+
+    Box::new(Thread {
+        name,
+        state: ThreadState::Ready,
+        saved_rsp,
+        _stack: Some(stack),
+        joiner: None,
+        detached: false,
+    })
+}
+
+pub(super) fn spawn(name: &'static str, start: ThreadStart) -> ThreadId {
+    let thread = new_thread(name, start);
+
+    let id = ThreadId::new();
+
+    interrupts::without_interrupts(|| {
+        let mut scheduler = SCHEDULER.lock();
+
+        let scheduler = scheduler
+            .as_mut()
+            .expect("thread::init() must be called before spawning threads!");
+
+        scheduler.reap_detached();
+
+        scheduler.threads.insert(id, thread);
+
+        // Keeps room in the ready queue for every thread (see `ready`).
+        let required = scheduler.threads.len();
+
+        scheduler.ready.reserve(required);
+        scheduler.ready.push_back(id);
+    });
+
+    id
+}
+
+impl Scheduler {
+    fn thread_mut(&mut self, id: ThreadId) -> &mut Thread {
+        self.threads.get_mut(&id).expect("No such thread.")
+    }
+
+    fn make_ready(&mut self, id: ThreadId) {
+        self.thread_mut(id).state = ThreadState::Ready;
+
+        if id != self.idle {
+            self.ready.push_back(id);
+        }
+    }
+
+    // Drops detached threads that have finished (never the current thread,
+    // whose stack we're still running on).
+    fn reap_detached(&mut self) {
+        let current = self.current;
+
+        self.threads.retain(|&id, thread| {
+            id == current || !(thread.detached && thread.state == ThreadState::Finished)
+        });
+    }
+
+    fn wake_sleepers(&mut self, now: u64) {
+        for (&id, thread) in self.threads.iter_mut() {
+            if let ThreadState::Sleeping(wake_tick) = thread.state {
+                if wake_tick <= now {
+                    thread.state = ThreadState::Ready;
+
+                    self.ready.push_back(id);
+                }
+            }
+        }
+    }
+}
+
+// Switches to the next ready thread, if there is one; otherwise keeps running
+// the current thread, or (if it can't run) the idle thread. Returns once this
+// thread is scheduled again.
+//
+// Must be called with interrupts disabled.
+pub(super) fn switch_to_next(mut guard: MutexGuard<'_, Option<Scheduler>>) {
+    let scheduler = guard.as_mut().unwrap();
+
+    let previous = scheduler.current;
+
+    let previous_is_runnable = scheduler.thread_mut(previous).state == ThreadState::Running;
+
+    let next = match scheduler.ready.pop_front() {
+        Some(next) => next,
+        None if previous_is_runnable => return,
+        None => scheduler.idle,
+    };
+
+    scheduler.slice_remaining = TIME_SLICE_TICKS;
+
+    if next == previous {
+        scheduler.thread_mut(previous).state = ThreadState::Running;
+
+        return;
+    }
+
+    if previous_is_runnable {
+        scheduler.make_ready(previous);
+    }
+
+    scheduler.current = next;
+
+    let next_thread = scheduler.thread_mut(next);
+
+    next_thread.state = ThreadState::Running;
+
+    let next_rsp = next_thread.saved_rsp;
+
+    let previous_rsp: *mut u64 = &mut scheduler.thread_mut(previous).saved_rsp;
+
+    // The lock must be released before we switch; the next thread may need it.
+    drop(guard);
+
+    unsafe { context::switch(previous_rsp, next_rsp) };
+}
+
+// Blocks the current thread in the given state, until something else makes it
+// ready again.
+pub(super) fn block_current(state: ThreadState) {
+    interrupts::without_interrupts(|| {
+        let mut guard = SCHEDULER.lock();
+
+        let scheduler = guard.as_mut().unwrap();
+
+        let current = scheduler.current;
+
+        scheduler.thread_mut(current).state = state;
+
+        switch_to_next(guard);
+    });
+}
+
+// Called from the timer interrupt handler (after its EOI), once per tick.
+pub(crate) fn on_timer_tick(now: u64) {
+    let mut guard = match SCHEDULER.try_lock() {
+        Some(guard) => guard,
+        None => return,
+    };
+
+    let scheduler = match guard.as_mut() {
+        Some(scheduler) => scheduler,
+        // Threads haven't been initialized yet.
+        None => return,
+    };
+
+    scheduler.wake_sleepers(now);
+
+    scheduler.slice_remaining = scheduler.slice_remaining.saturating_sub(1);
+
+    if scheduler.slice_remaining == 0 {
+        switch_to_next(guard);
+    }
+}
+
+// Waits for the given thread to finish, then frees it.
+pub(super) fn join(id: ThreadId) {
+    loop {
+        let finished = interrupts::without_interrupts(|| {
+            let mut guard = SCHEDULER.lock();
+
+            let scheduler = guard.as_mut().unwrap();
+
+            let current = scheduler.current;
+
+            let thread = scheduler.thread_mut(id);
+
+            if thread.state == ThreadState::Finished {
+                scheduler.threads.remove(&id);
+
+                return true;
+            }
+
+            thread.joiner = Some(current);
+
+            scheduler.thread_mut(current).state = ThreadState::Joining;
+
+            switch_to_next(guard);
+
+            false
+        });
+
+        if finished {
+            return;
+        }
+    }
+}
+
+// Marks the given thread as detached; it'll be freed once it finishes.
+pub(super) fn detach(id: ThreadId) {
+    interrupts::without_interrupts(|| {
+        let mut guard = SCHEDULER.lock();
+
+        let scheduler = guard.as_mut().unwrap();
+
+        scheduler.thread_mut(id).detached = true;
+
+        scheduler.reap_detached();
+    });
+}
+
+// Finishes the current thread, waking its joiner (if any). Never returns.
+fn exit_current() -> ! {
+    interrupts::disable();
+
+    let mut guard = SCHEDULER.lock();
+
+    let scheduler = guard.as_mut().unwrap();
+
+    let current = scheduler.current;
+
+    let thread = scheduler.thread_mut(current);
+
+    thread.state = ThreadState::Finished;
+
+    if let Some(joiner) = thread.joiner.take() {
+        scheduler.make_ready(joiner);
+    }
+
+    switch_to_next(guard);
+
+    unreachable!("Finished thread was scheduled again.");
+}
+
+// Where every spawned thread starts running (via the trampoline in
+// `context`), with interrupts still disabled from the switch that got here.
+pub(super) extern "C" fn thread_entry(start: *mut ThreadStart) -> ! {
+    let start = unsafe { Box::from_raw(start) };
+
+    interrupts::enable();
+
+    start();
+
+    exit_current();
+}
@@ -0,0 +1,151 @@
+use core::{
+    fmt,
+    ops::{Add, Sub},
+    sync::atomic::{AtomicU64, AtomicU8, Ordering},
+    time::Duration,
+};
+
+pub mod hpet;
+pub mod pit;
+pub mod rtc;
+pub mod tsc;
+
+// Rate at which the timer interrupt (IRQ0) fires, regardless of its source.
+pub const TICKS_PER_SECOND: u64 = 100;
+
+// Number of timer interrupts received since boot.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+static TICK_SOURCE: AtomicU8 = AtomicU8::new(TickSource::Pit as u8);
+
+// The device driving the timer interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TickSource {
+    Pit = 0,
+    Hpet = 1,
+}
+
+// A monotonic, nanosecond-resolution timestamp, backed by the CPU's
+// time-stamp counter (TSC). Instants are only meaningful relative to one
+// another, e.g., for profiling a section of code or timing a benchmark.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    cycles: u64,
+}
+
+impl Instant {
+    // Reads the current value of the time-stamp counter.
+    pub fn now() -> Self {
+        Instant {
+            cycles: tsc::read(),
+        }
+    }
+
+    // Returns the amount of time elapsed since this instant was created.
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(*self)
+    }
+
+    // Returns the amount of time elapsed from `earlier` to `self`, saturating
+    // to zero if `earlier` is actually later than `self`.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        tsc::cycles_to_duration(self.cycles.saturating_sub(earlier.cycles))
+    }
+
+    // Returns the raw TSC value captured by this instant.
+    pub fn as_cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    // Rebuilds an instant from a TSC value captured by `as_cycles()` (e.g.,
+    // one stashed in an atomic).
+    pub fn from_cycles(cycles: u64) -> Self {
+        Instant { cycles }
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        Instant {
+            cycles: self.cycles + tsc::duration_to_cycles(duration),
+        }
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+impl fmt::Debug for Instant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Instant({} cycles)", self.cycles)
+    }
+}
+
+// Called by the timer interrupt handler, once per tick.
+pub(crate) fn tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+// Time elapsed since boot, at tick granularity.
+pub fn uptime() -> Duration {
+    Duration::from_nanos(ticks() * (1_000_000_000 / TICKS_PER_SECOND))
+}
+
+pub fn tick_source() -> TickSource {
+    match TICK_SOURCE.load(Ordering::Relaxed) {
+        1 => TickSource::Hpet,
+        _ => TickSource::Pit,
+    }
+}
+
+// Switches the device that drives the timer interrupt. Switching to the HPET
+// requires that `hpet::init()` has already succeeded.
+pub fn set_tick_source(source: TickSource) -> Result<(), hpet::HpetError> {
+    let period = Duration::from_nanos(1_000_000_000 / TICKS_PER_SECOND);
+
+    match source {
+        TickSource::Hpet => {
+            hpet::set_periodic(0, period)?;
+            hpet::set_legacy_replacement(true)?;
+        }
+        TickSource::Pit => {
+            if hpet::is_available() {
+                hpet::set_legacy_replacement(false)?;
+                hpet::disable_timer(0)?;
+            }
+
+            unsafe { pit::set_frequency(TICKS_PER_SECOND) };
+        }
+    }
+
+    TICK_SOURCE.store(source as u8, Ordering::Relaxed);
+
+    Ok(())
+}
+
+pub fn init() {
+    tsc::init();
+
+    unsafe { pit::set_frequency(TICKS_PER_SECOND) };
+}
+
+#[test_case]
+fn test_instant_is_monotonic() {
+    let earlier = Instant::now();
+    let later = Instant::now();
+
+    assert!(later >= earlier);
+    assert_eq!(earlier.duration_since(later), Duration::ZERO);
+}
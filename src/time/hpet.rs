@@ -0,0 +1,290 @@
+use core::{
+    ptr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use x86_64::{
+    structures::paging::{mapper::MapToError, FrameAllocator, Mapper, Size4KiB},
+    PhysAddr, VirtAddr,
+};
+
+use crate::{
+    acpi::{self, AcpiError, GenericAddress, SdtHeader},
+    memory,
+};
+
+// Errors that may occur while initializing or programming the HPET.
+#[derive(Debug)]
+pub enum HpetError {
+    Acpi(AcpiError),
+    Mapping(MapToError<Size4KiB>),
+    NotMemoryMapped,
+    LegacyRoutingUnsupported,
+    PeriodicModeUnsupported(u8),
+    InvalidTimer(u8),
+    Uninitialized,
+}
+
+// The ACPI "HPET" description table.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct HpetTable {
+    header: SdtHeader,
+    event_timer_block_id: u32,
+    base_address: GenericAddress,
+    hpet_number: u8,
+    minimum_tick: u16,
+    page_protection: u8,
+}
+
+// Register offsets, relative to the HPET's base address.
+const GENERAL_CAPABILITIES: usize = 0x000;
+const GENERAL_CONFIGURATION: usize = 0x010;
+const MAIN_COUNTER: usize = 0x0f0;
+
+const fn timer_configuration(timer: u8) -> usize {
+    0x100 + 0x20 * timer as usize
+}
+
+const fn timer_comparator(timer: u8) -> usize {
+    0x108 + 0x20 * timer as usize
+}
+
+// General capabilities register fields.
+const LEGACY_ROUTE_CAPABLE: u64 = 1 << 15;
+
+// General configuration register fields.
+const ENABLE: u64 = 1 << 0;
+const LEGACY_ROUTE: u64 = 1 << 1;
+
+// Timer N configuration register fields.
+const TIMER_INTERRUPT_ENABLE: u64 = 1 << 2;
+const TIMER_PERIODIC: u64 = 1 << 3;
+const TIMER_PERIODIC_CAPABLE: u64 = 1 << 4;
+const TIMER_SET_ACCUMULATOR: u64 = 1 << 6;
+
+const FEMTOSECONDS_PER_NANOSECOND: u128 = 1_000_000;
+
+// Virtual address of the HPET's register block; zero until `init()` succeeds.
+static BASE: AtomicU64 = AtomicU64::new(0);
+
+// Main counter tick period, in femtoseconds.
+static PERIOD_FS: AtomicU64 = AtomicU64::new(0);
+
+fn base() -> Result<u64, HpetError> {
+    match BASE.load(Ordering::Relaxed) {
+        0 => Err(HpetError::Uninitialized),
+        base => Ok(base),
+    }
+}
+
+unsafe fn read_register(base: u64, offset: usize) -> u64 {
+    ptr::read_volatile((base as usize + offset) as *const u64)
+}
+
+unsafe fn write_register(base: u64, offset: usize, value: u64) {
+    ptr::write_volatile((base as usize + offset) as *mut u64, value)
+}
+
+// Locates the HPET through its ACPI table, maps its registers, and starts the
+// main counter. Timers remain disabled until they're explicitly programmed.
+pub fn init(
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<(), HpetError> {
+    let table = unsafe { acpi::read_table::<HpetTable>(b"HPET") }.map_err(HpetError::Acpi)?;
+
+    let base_address = table.base_address;
+
+    if base_address.address_space_id != acpi::ADDRESS_SPACE_SYSTEM_MEMORY {
+        return Err(HpetError::NotMemoryMapped);
+    }
+
+    let base = unsafe {
+        memory::map_mmio(
+            PhysAddr::new(base_address.address),
+            mapper,
+            frame_allocator,
+        )
+    }
+    .map_err(HpetError::Mapping)?
+    .as_u64();
+
+    unsafe {
+        let capabilities = read_register(base, GENERAL_CAPABILITIES);
+
+        // Without an I/O APIC, legacy replacement routing is the only way to
+        // deliver HPET interrupts through the 8259 PICs.
+        if capabilities & LEGACY_ROUTE_CAPABLE == 0 {
+            memory::unmap_mmio(VirtAddr::new(base), mapper);
+
+            return Err(HpetError::LegacyRoutingUnsupported);
+        }
+
+        PERIOD_FS.store(capabilities >> 32, Ordering::Relaxed);
+
+        // Halts the main counter while we reset it and disable all timers.
+        write_register(base, GENERAL_CONFIGURATION, 0);
+        write_register(base, MAIN_COUNTER, 0);
+
+        for timer in 0..timer_count_from(capabilities) {
+            let configuration = read_register(base, timer_configuration(timer));
+
+            write_register(
+                base,
+                timer_configuration(timer),
+                configuration & !(TIMER_INTERRUPT_ENABLE | TIMER_PERIODIC),
+            );
+        }
+
+        write_register(base, GENERAL_CONFIGURATION, ENABLE);
+    }
+
+    BASE.store(base, Ordering::Relaxed);
+
+    Ok(())
+}
+
+pub fn is_available() -> bool {
+    base().is_ok()
+}
+
+fn timer_count_from(capabilities: u64) -> u8 {
+    (((capabilities >> 8) & 0x1f) + 1) as u8
+}
+
+// Number of comparators (timers) implemented by this HPET.
+pub fn timer_count() -> Result<u8, HpetError> {
+    let base = base()?;
+
+    Ok(timer_count_from(unsafe {
+        read_register(base, GENERAL_CAPABILITIES)
+    }))
+}
+
+// Frequency of the main counter, in Hz.
+pub fn frequency_hz() -> Result<u64, HpetError> {
+    base()?;
+
+    Ok(1_000_000_000_000_000 / PERIOD_FS.load(Ordering::Relaxed))
+}
+
+// Reads the main counter.
+pub fn counter() -> Result<u64, HpetError> {
+    let base = base()?;
+
+    Ok(unsafe { read_register(base, MAIN_COUNTER) })
+}
+
+fn duration_to_ticks(duration: Duration) -> u64 {
+    let period_fs = PERIOD_FS.load(Ordering::Relaxed) as u128;
+
+    ((duration.as_nanos() * FEMTOSECONDS_PER_NANOSECOND) / period_fs).max(1) as u64
+}
+
+fn checked_timer(base: u64, timer: u8) -> Result<(), HpetError> {
+    let capabilities = unsafe { read_register(base, GENERAL_CAPABILITIES) };
+
+    if timer >= timer_count_from(capabilities) {
+        return Err(HpetError::InvalidTimer(timer));
+    }
+
+    Ok(())
+}
+
+// Routes timer 0 to IRQ0 and timer 1 to IRQ8 (replacing the PIT and the RTC's
+// periodic interrupt, respectively), or restores the legacy devices' routing.
+pub fn set_legacy_replacement(enabled: bool) -> Result<(), HpetError> {
+    let base = base()?;
+
+    unsafe {
+        let configuration = read_register(base, GENERAL_CONFIGURATION);
+
+        let configuration = if enabled {
+            configuration | LEGACY_ROUTE
+        } else {
+            configuration & !LEGACY_ROUTE
+        };
+
+        write_register(base, GENERAL_CONFIGURATION, configuration);
+    }
+
+    Ok(())
+}
+
+// Programs the given timer to fire an interrupt every `period`.
+pub fn set_periodic(timer: u8, period: Duration) -> Result<(), HpetError> {
+    let base = base()?;
+
+    checked_timer(base, timer)?;
+
+    let ticks = duration_to_ticks(period);
+
+    unsafe {
+        let configuration = read_register(base, timer_configuration(timer));
+
+        if configuration & TIMER_PERIODIC_CAPABLE == 0 {
+            return Err(HpetError::PeriodicModeUnsupported(timer));
+        }
+
+        // With the accumulator bit set, the first comparator write sets the
+        // time of the first interrupt, and the second sets the period.
+        write_register(
+            base,
+            timer_configuration(timer),
+            configuration | TIMER_INTERRUPT_ENABLE | TIMER_PERIODIC | TIMER_SET_ACCUMULATOR,
+        );
+
+        let now = read_register(base, MAIN_COUNTER);
+
+        write_register(base, timer_comparator(timer), now.wrapping_add(ticks));
+        write_register(base, timer_comparator(timer), ticks);
+    }
+
+    Ok(())
+}
+
+// Programs the given timer to fire a single interrupt after `delay`.
+pub fn set_one_shot(timer: u8, delay: Duration) -> Result<(), HpetError> {
+    let base = base()?;
+
+    checked_timer(base, timer)?;
+
+    let ticks = duration_to_ticks(delay);
+
+    unsafe {
+        let configuration = read_register(base, timer_configuration(timer));
+
+        write_register(
+            base,
+            timer_configuration(timer),
+            (configuration & !TIMER_PERIODIC) | TIMER_INTERRUPT_ENABLE,
+        );
+
+        let now = read_register(base, MAIN_COUNTER);
+
+        write_register(base, timer_comparator(timer), now.wrapping_add(ticks));
+    }
+
+    Ok(())
+}
+
+// Stops the given timer from generating interrupts.
+pub fn disable_timer(timer: u8) -> Result<(), HpetError> {
+    let base = base()?;
+
+    checked_timer(base, timer)?;
+
+    unsafe {
+        let configuration = read_register(base, timer_configuration(timer));
+
+        write_register(
+            base,
+            timer_configuration(timer),
+            configuration & !(TIMER_INTERRUPT_ENABLE | TIMER_PERIODIC),
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use x86_64::instructions::port::Port;
+
+// The 8253/8254 programmable interval timer (PIT) is driven by a fixed
+// 1.193182 MHz oscillator, regardless of the CPU's clock speed.
+pub const PIT_FREQUENCY_HZ: u64 = 1_193_182;
+
+const CHANNEL_0_DATA_PORT: u16 = 0x40;
+const CHANNEL_2_DATA_PORT: u16 = 0x42;
+const COMMAND_PORT: u16 = 0x43;
+
+// Port 0x61 (the "NMI status and control" port) exposes the gate input of
+// channel 2 (bit 0), the PC speaker enable (bit 1), and the output of channel
+// 2 (bit 5).
+const CONTROL_PORT: u16 = 0x61;
+
+const CHANNEL_2_GATE: u8 = 1 << 0;
+const SPEAKER_ENABLE: u8 = 1 << 1;
+const CHANNEL_2_OUTPUT: u8 = 1 << 5;
+
+// Programs channel 0 (wired to IRQ0) to fire at (approximately) the given
+// frequency, in Hz.
+//
+// Unsafe because changing the IRQ0 rate affects anything that counts ticks.
+pub unsafe fn set_frequency(hz: u64) {
+    let mut command_port: Port<u8> = Port::new(COMMAND_PORT);
+    let mut channel_0_port: Port<u8> = Port::new(CHANNEL_0_DATA_PORT);
+
+    let divisor = (PIT_FREQUENCY_HZ / hz).clamp(1, u16::MAX as u64) as u16;
+
+    // Channel 0, access mode lobyte/hibyte, mode 3 (square wave generator),
+    // binary counting.
+    command_port.write(0b0011_0110);
+
+    channel_0_port.write((divisor & 0xff) as u8);
+    channel_0_port.write((divisor >> 8) as u8);
+}
+
+// Busy-waits for (approximately) the given number of PIT ticks, using channel
+// 2 in one-shot mode so that we don't disturb the channel 0 (IRQ0) timer. The
+// PC speaker is kept disconnected for the duration.
+//
+// Unsafe because the caller must ensure that nothing else is using channel 2.
+pub unsafe fn wait_ticks(ticks: u16) {
+    let mut control_port: Port<u8> = Port::new(CONTROL_PORT);
+    let mut command_port: Port<u8> = Port::new(COMMAND_PORT);
+    let mut channel_2_port: Port<u8> = Port::new(CHANNEL_2_DATA_PORT);
+
+    // Raises the channel 2 gate, and disconnects the speaker.
+    let control = control_port.read();
+
+    control_port.write((control & !SPEAKER_ENABLE) | CHANNEL_2_GATE);
+
+    // Channel 2, access mode lobyte/hibyte, mode 0 (interrupt on terminal
+    // count), binary counting.
+    command_port.write(0b1011_0000);
+
+    channel_2_port.write((ticks & 0xff) as u8);
+    channel_2_port.write((ticks >> 8) as u8);
+
+    // The channel's output goes high once the count reaches zero.
+    while control_port.read() & CHANNEL_2_OUTPUT == 0 {
+        core::hint::spin_loop();
+    }
+
+    // Restores the original gate and speaker configuration.
+    control_port.write(control);
+}
@@ -0,0 +1,269 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use x86_64::instructions::{interrupts, port::Port};
+
+const CMOS_ADDRESS_PORT: u16 = 0x70;
+const CMOS_DATA_PORT: u16 = 0x71;
+
+// Setting the top bit of the CMOS address disables NMIs while we access the
+// RTC, so that an NMI can't leave the RTC in an undefined state. The bit is
+// cleared again once each access is done (see `enable_nmi()`).
+const NMI_DISABLE: u8 = 1 << 7;
+
+const REGISTER_SECONDS: u8 = 0x00;
+const REGISTER_MINUTES: u8 = 0x02;
+const REGISTER_HOURS: u8 = 0x04;
+const REGISTER_DAY: u8 = 0x07;
+const REGISTER_MONTH: u8 = 0x08;
+const REGISTER_YEAR: u8 = 0x09;
+const REGISTER_STATUS_A: u8 = 0x0a;
+const REGISTER_STATUS_B: u8 = 0x0b;
+const REGISTER_STATUS_C: u8 = 0x0c;
+
+// Status register A fields.
+const UPDATE_IN_PROGRESS: u8 = 1 << 7;
+
+// Status register B fields.
+const HOUR_FORMAT_24: u8 = 1 << 1;
+const BINARY_MODE: u8 = 1 << 2;
+const PERIODIC_INTERRUPT_ENABLE: u8 = 1 << 6;
+
+// In 12-hour mode, the top bit of the hours register marks PM.
+const HOUR_PM: u8 = 1 << 7;
+
+// The RTC doesn't reliably report the century; we assume the 21st.
+const CENTURY: u16 = 2000;
+
+// Number of periodic (IRQ8) interrupts received since they were enabled.
+static PERIODIC_INTERRUPTS: AtomicU64 = AtomicU64::new(0);
+
+// A calendar date and time of day, as reported by the RTC (usually UTC, but
+// that's up to the firmware's configuration).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+unsafe fn read_register(register: u8) -> u8 {
+    let mut address_port: Port<u8> = Port::new(CMOS_ADDRESS_PORT);
+    let mut data_port: Port<u8> = Port::new(CMOS_DATA_PORT);
+
+    address_port.write(NMI_DISABLE | register);
+
+    let value = data_port.read();
+
+    enable_nmi(&mut address_port, register);
+
+    value
+}
+
+unsafe fn write_register(register: u8, value: u8) {
+    let mut address_port: Port<u8> = Port::new(CMOS_ADDRESS_PORT);
+    let mut data_port: Port<u8> = Port::new(CMOS_DATA_PORT);
+
+    address_port.write(NMI_DISABLE | register);
+
+    data_port.write(value);
+
+    enable_nmi(&mut address_port, register);
+}
+
+// Clears the NMI-disable bit (which otherwise stays set until the next write
+// to the address port), leaving `register` selected.
+unsafe fn enable_nmi(address_port: &mut Port<u8>, register: u8) {
+    address_port.write(register);
+}
+
+fn bcd_to_binary(value: u8) -> u8 {
+    (value & 0x0f) + (value >> 4) * 10
+}
+
+// Raw register values, exactly as read from the RTC.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct RawDateTime {
+    second: u8,
+    minute: u8,
+    hour: u8,
+    day: u8,
+    month: u8,
+    year: u8,
+}
+
+unsafe fn read_raw() -> RawDateTime {
+    // Waits for any in-progress update to finish, so that we don't read a mix
+    // of old and new values.
+    while read_register(REGISTER_STATUS_A) & UPDATE_IN_PROGRESS != 0 {
+        core::hint::spin_loop();
+    }
+
+    RawDateTime {
+        second: read_register(REGISTER_SECONDS),
+        minute: read_register(REGISTER_MINUTES),
+        hour: read_register(REGISTER_HOURS),
+        day: read_register(REGISTER_DAY),
+        month: read_register(REGISTER_MONTH),
+        year: read_register(REGISTER_YEAR),
+    }
+}
+
+// Reads the current date and time from the RTC.
+pub fn now() -> DateTime {
+    let (raw, status_b) = interrupts::without_interrupts(|| unsafe {
+        // An update may still begin between our check of the update flag and
+        // our reads, so we read until two consecutive samples agree.
+        let mut raw = read_raw();
+
+        loop {
+            let next = read_raw();
+
+            if next == raw {
+                break;
+            }
+
+            raw = next;
+        }
+
+        (raw, read_register(REGISTER_STATUS_B))
+    });
+
+    decode(raw, status_b)
+}
+
+// Converts raw register values to a `DateTime`, according to the data mode and
+// hour format in status register B.
+fn decode(raw: RawDateTime, status_b: u8) -> DateTime {
+    let is_pm = raw.hour & HOUR_PM != 0;
+
+    let decode_value = |value: u8| {
+        if status_b & BINARY_MODE != 0 {
+            value
+        } else {
+            bcd_to_binary(value)
+        }
+    };
+
+    let mut hour = decode_value(raw.hour & !HOUR_PM);
+
+    // Converts 12-hour time (1..=12, plus a PM flag) to 24-hour time.
+    if status_b & HOUR_FORMAT_24 == 0 {
+        hour = match (hour, is_pm) {
+            (12, false) => 0,
+            (12, true) => 12,
+            (hour, true) => hour + 12,
+            (hour, false) => hour,
+        };
+    }
+
+    DateTime {
+        year: CENTURY + decode_value(raw.year) as u16,
+        month: decode_value(raw.month),
+        day: decode_value(raw.day),
+        hour,
+        minute: decode_value(raw.minute),
+        second: decode_value(raw.second),
+    }
+}
+
+// Enables the RTC's periodic interrupt (IRQ8), at a frequency of
+// `32768 >> (rate - 1)` Hz; `rate` must be in 3..=15 (8 kHz down to 2 Hz).
+//
+// Note that this conflicts with the HPET: its legacy replacement routing, which
+// `time::set_tick_source(TickSource::Hpet)` enables, hands IRQ8 to HPET timer
+// 1, so the RTC's interrupts never arrive while the HPET drives the tick.
+pub fn enable_periodic_interrupt(rate: u8) {
+    assert!((3..=15).contains(&rate), "Invalid RTC periodic rate {}.", rate);
+
+    interrupts::without_interrupts(|| unsafe {
+        let status_a = read_register(REGISTER_STATUS_A);
+
+        write_register(REGISTER_STATUS_A, (status_a & 0xf0) | rate);
+
+        let status_b = read_register(REGISTER_STATUS_B);
+
+        write_register(REGISTER_STATUS_B, status_b | PERIODIC_INTERRUPT_ENABLE);
+
+        // Discards any interrupt that was already pending.
+        read_register(REGISTER_STATUS_C);
+    });
+
+    crate::interrupts::unmask_irq(crate::interrupts::InterruptIndex::Rtc);
+}
+
+pub fn disable_periodic_interrupt() {
+    interrupts::without_interrupts(|| unsafe {
+        let status_b = read_register(REGISTER_STATUS_B);
+
+        write_register(REGISTER_STATUS_B, status_b & !PERIODIC_INTERRUPT_ENABLE);
+    });
+}
+
+pub fn periodic_interrupt_count() -> u64 {
+    PERIODIC_INTERRUPTS.load(Ordering::Relaxed)
+}
+
+// Called by the RTC interrupt handler.
+pub(crate) fn handle_interrupt() {
+    // The RTC won't raise another interrupt until status register C is read.
+    unsafe { read_register(REGISTER_STATUS_C) };
+
+    PERIODIC_INTERRUPTS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[test_case]
+fn test_now_is_plausible() {
+    let now = now();
+
+    assert!(now.year >= 2000);
+    assert!((1..=12).contains(&now.month));
+    assert!((1..=31).contains(&now.day));
+    assert!(now.hour < 24);
+    assert!(now.minute < 60);
+    assert!(now.second < 60);
+}
+
+#[cfg(test)]
+fn raw_with_hour(hour: u8) -> RawDateTime {
+    RawDateTime {
+        second: 0x59,
+        minute: 0x30,
+        hour,
+        day: 0x01,
+        month: 0x01,
+        year: 0x24,
+    }
+}
+
+#[test_case]
+fn test_decode_bcd() {
+    let date_time = decode(raw_with_hour(0x23), HOUR_FORMAT_24);
+
+    assert_eq!(date_time.second, 59);
+    assert_eq!(date_time.minute, 30);
+    assert_eq!(date_time.hour, 23);
+    assert_eq!(date_time.year, 2024);
+}
+
+#[test_case]
+fn test_decode_12_hour() {
+    assert_eq!(decode(raw_with_hour(0x12 | HOUR_PM), 0).hour, 12);
+    assert_eq!(decode(raw_with_hour(0x12), 0).hour, 0);
+    assert_eq!(decode(raw_with_hour(0x01 | HOUR_PM), 0).hour, 13);
+}
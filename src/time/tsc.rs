@@ -0,0 +1,98 @@
+use core::{
+    arch::x86_64::{__cpuid, _rdtsc},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use super::pit;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+// Length of the PIT-timed window we measure the TSC across; longer windows
+// give a more accurate frequency estimate, at the cost of boot time.
+const CALIBRATION_MILLIS: u64 = 10;
+
+// Calibrated TSC frequency, in cycles per second; zero until `init()` runs.
+static FREQUENCY_HZ: AtomicU64 = AtomicU64::new(0);
+
+static INVARIANT: AtomicBool = AtomicBool::new(false);
+
+// Reads the current value of the time-stamp counter.
+pub fn read() -> u64 {
+    unsafe { _rdtsc() }
+}
+
+// Returns the calibrated TSC frequency (in Hz), if calibration has happened.
+pub fn frequency_hz() -> Option<u64> {
+    match FREQUENCY_HZ.load(Ordering::Relaxed) {
+        0 => None,
+        hz => Some(hz),
+    }
+}
+
+// Whether the TSC ticks at a constant rate across all P-, C- and T-states. A
+// TSC that isn't invariant is still usable for short measurements, but may
+// drift if the CPU changes frequency.
+pub fn is_invariant() -> bool {
+    INVARIANT.load(Ordering::Relaxed)
+}
+
+pub(crate) fn cycles_to_duration(cycles: u64) -> Duration {
+    match frequency_hz() {
+        Some(hz) => {
+            let nanos = (cycles as u128 * NANOS_PER_SEC) / hz as u128;
+
+            Duration::from_nanos(nanos as u64)
+        }
+        // Uncalibrated; we can't say anything meaningful.
+        None => Duration::ZERO,
+    }
+}
+
+pub(crate) fn duration_to_cycles(duration: Duration) -> u64 {
+    match frequency_hz() {
+        Some(hz) => ((duration.as_nanos() * hz as u128) / NANOS_PER_SEC) as u64,
+        None => 0,
+    }
+}
+
+// Detects whether the TSC is invariant, and calibrates its frequency against
+// the PIT. Calling this more than once is harmless (it's a no-op).
+pub fn init() {
+    if frequency_hz().is_some() {
+        return;
+    }
+
+    INVARIANT.store(detect_invariant_tsc(), Ordering::Relaxed);
+
+    FREQUENCY_HZ.store(calibrate_against_pit(), Ordering::Relaxed);
+}
+
+fn detect_invariant_tsc() -> bool {
+    // CPUID leaf 0x8000_0000 reports the highest supported extended leaf.
+    let max_extended_leaf = unsafe { __cpuid(0x8000_0000) }.eax;
+
+    if max_extended_leaf < 0x8000_0007 {
+        return false;
+    }
+
+    // Leaf 0x8000_0007 ("Advanced Power Management Information"), EDX bit 8.
+    let advanced_power_management = unsafe { __cpuid(0x8000_0007) };
+
+    advanced_power_management.edx & (1 << 8) != 0
+}
+
+fn calibrate_against_pit() -> u64 {
+    let pit_ticks = (pit::PIT_FREQUENCY_HZ * CALIBRATION_MILLIS / 1000) as u16;
+
+    // We don't want an interrupt handler to stretch our measurement window.
+    let cycles = x86_64::instructions::interrupts::without_interrupts(|| {
+        let start = read();
+
+        unsafe { pit::wait_ticks(pit_ticks) };
+
+        read() - start
+    });
+
+    cycles * pit::PIT_FREQUENCY_HZ / pit_ticks as u64
+}
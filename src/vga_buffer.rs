@@ -1,4 +1,4 @@
-use core::fmt;
+use core::{fmt, ops::Range};
 
 use volatile::Volatile;
 
@@ -6,6 +6,15 @@ use lazy_static::lazy_static;
 
 use spin::Mutex;
 
+use self::{
+    ansi::{Action, ControlSequence, Parser},
+    cursor::Cursor,
+};
+
+mod ansi;
+pub mod cp437;
+pub mod cursor;
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -36,6 +45,45 @@ impl ColorCode {
     fn new(foreground: Color, background: Color) -> ColorCode {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
+
+    fn foreground(self) -> u8 {
+        self.0 & 0x0f
+    }
+
+    fn background(self) -> u8 {
+        self.0 >> 4
+    }
+
+    fn with_foreground(self, foreground: u8) -> ColorCode {
+        ColorCode(self.0 & 0xf0 | foreground & 0x0f)
+    }
+
+    // Note that, unless the VGA's blink attribute is turned off, bright
+    // backgrounds (8-15) make the text blink instead.
+    fn with_background(self, background: u8) -> ColorCode {
+        ColorCode((background & 0x0f) << 4 | self.0 & 0x0f)
+    }
+}
+
+// The eight ANSI colors, in SGR order (30-37 and 40-47). Their bright variants
+// (90-97 and 100-107) are the VGA colors 8 higher.
+const ANSI_COLORS: [Color; 8] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Brown,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::LightGray,
+];
+
+// Maps an ANSI color number (0-7, or 8-15 for the bright variants) to its
+// VGA color number.
+fn ansi_color(number: u16) -> u8 {
+    let bright = if number & 8 != 0 { 8 } else { 0 };
+
+    ANSI_COLORS[(number & 7) as usize] as u8 | bright
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -46,80 +94,358 @@ struct ScreenChar {
 }
 
 const BUFFER_HEIGHT: usize = 25;
-const BUFFER_WIDTH: usize = 80;
+pub const BUFFER_WIDTH: usize = 80;
 
 #[repr(transparent)]
 struct Buffer {
     chars: [[Volatile<ScreenChar>; BUFFER_WIDTH]; BUFFER_HEIGHT],
 }
 
+// The state saved by ESC 7 (or CSI s), and restored by ESC 8 (or CSI u).
+#[derive(Debug, Clone, Copy)]
+struct SavedCursor {
+    row: usize,
+    column: usize,
+    color_code: ColorCode,
+    bold: bool,
+}
+
 pub struct Writer {
+    row_position: usize,
     column_position: usize,
     color_code: ColorCode,
+    // The colors that SGR 0 (reset) returns to.
+    default_color_code: ColorCode,
+    // Bold (SGR 1) text is shown in the bright variant of its color.
+    bold: bool,
+    saved_cursor: Option<SavedCursor>,
+    parser: Parser,
     buffer: &'static mut Buffer,
+    cursor: Cursor,
 }
 
 impl Writer {
+    // Writes a single CP437 byte, treating b'\n' as a newline. This doesn't
+    // move the hardware cursor, so that writing a string only does so once,
+    // at the end; callers writing bytes directly should finish with
+    // `set_column_position()` or `new_line()`.
     pub fn write_byte(&mut self, byte: u8) {
         match byte {
             b'\n' => self.new_line(),
-            byte => {
-                if self.column_position >= BUFFER_WIDTH {
-                    self.new_line();
-                }
+            byte => self.write_glyph(byte),
+        }
+    }
 
-                let row = BUFFER_HEIGHT - 1;
-                let col = self.column_position;
+    // Like `write_byte()`, but always draws the byte's glyph: 0x0a is '◙'
+    // here, not a newline. Wraps to the next row once the current one is
+    // full.
+    pub fn write_glyph(&mut self, glyph: u8) {
+        if self.column_position >= BUFFER_WIDTH {
+            self.new_line();
+        }
 
-                let color_code = self.color_code;
+        let row = self.row_position;
+        let col = self.column_position;
 
-                self.buffer.chars[row][col].write(ScreenChar {
-                    ascii_character: byte,
-                    color_code,
-                });
+        let color_code = self.color_code;
 
-                self.column_position += 1;
-            }
-        }
+        self.buffer.chars[row][col].write(ScreenChar {
+            ascii_character: glyph,
+            color_code,
+        });
+
+        self.column_position += 1;
     }
 
+    // Writes `s`, interpreting any ANSI escape sequences in it.
     pub fn write_string(&mut self, s: &str) {
-        for byte in s.bytes() {
-            match byte {
-                // Printable byte
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                // Non-printable byte
-                _ => self.write_byte(0xfe),
+        self.write_chars(s);
+
+        self.sync_cursor();
+    }
+
+    fn write_chars(&mut self, s: &str) {
+        for character in s.chars() {
+            match self.parser.advance(character) {
+                Some(Action::Print(character)) => self.write_char(character),
+                Some(Action::Escape(character)) => self.escape(character),
+                Some(Action::Control(sequence)) => self.control_sequence(&sequence),
+                None => {}
             }
         }
     }
 
-    pub fn new_line(&mut self) {
-        for row in 1..BUFFER_HEIGHT {
-            for col in 0..BUFFER_WIDTH {
-                let character = self.buffer.chars[row][col].read();
+    fn write_char(&mut self, character: char) {
+        match character {
+            '\n' => self.new_line(),
+            '\r' => self.column_position = 0,
+            '\u{8}' => self.column_position = self.column_position.saturating_sub(1),
+            // Anything else is shown as its CP437 glyph (or a replacement
+            // character, if it doesn't have one).
+            character => self.write_glyph(cp437::encode(character)),
+        }
+    }
 
-                self.buffer.chars[row - 1][col].write(character);
+    // Moves to the start of the next row, scrolling if we're on the bottom
+    // row.
+    pub fn new_line(&mut self) {
+        if self.row_position < BUFFER_HEIGHT - 1 {
+            self.row_position += 1;
+        } else {
+            for row in 1..BUFFER_HEIGHT {
+                for col in 0..BUFFER_WIDTH {
+                    let character = self.buffer.chars[row][col].read();
+
+                    self.buffer.chars[row - 1][col].write(character);
+                }
             }
-        }
 
-        self.clear_row(BUFFER_HEIGHT - 1);
+            self.clear_row(BUFFER_HEIGHT - 1);
+        }
 
         self.column_position = 0;
+
+        self.sync_cursor();
+    }
+
+    // The column (on the current row) that the next byte will be written to.
+    pub fn column_position(&self) -> usize {
+        self.column_position
+    }
+
+    // Moves the write position within the current row, e.g. for redrawing a
+    // line that's being edited.
+    pub fn set_column_position(&mut self, column: usize) {
+        self.column_position = column.min(BUFFER_WIDTH);
+
+        self.sync_cursor();
+    }
+
+    // Moves the write position, clamped to the screen (rows and columns are
+    // counted from 0).
+    fn move_to(&mut self, row: usize, column: usize) {
+        self.row_position = row.min(BUFFER_HEIGHT - 1);
+        self.column_position = column.min(BUFFER_WIDTH - 1);
+    }
+
+    // The hardware cursor, e.g. for showing, hiding or reshaping it. Its
+    // position follows the write position.
+    pub fn cursor(&mut self) -> &mut Cursor {
+        &mut self.cursor
+    }
+
+    // Moves the hardware cursor to where the next byte will be written. Once
+    // a row is full, that's the start of the next row, but the cursor stays at
+    // the end of the current one (which may not have been scrolled yet).
+    fn sync_cursor(&mut self) {
+        let column = self.column_position.min(BUFFER_WIDTH - 1);
+
+        self.cursor.set_position(self.row_position * BUFFER_WIDTH + column);
+    }
+
+    // Blanks the current row from `column` to its end, without moving the
+    // write position.
+    pub fn clear_to_end_of_line(&mut self, column: usize) {
+        self.clear(self.row_position, column..BUFFER_WIDTH);
     }
 
     fn clear_row(&mut self, row: usize) {
+        self.clear(row, 0..BUFFER_WIDTH);
+    }
+
+    fn clear(&mut self, row: usize, columns: Range<usize>) {
         let blank = ScreenChar {
             ascii_character: b' ',
             color_code: self.color_code,
         };
 
-        for col in 0..BUFFER_WIDTH {
+        for col in columns {
             self.buffer.chars[row][col].write(blank);
         }
     }
+
+    // Handles a two-character escape sequence (ESC followed by `character`).
+    fn escape(&mut self, character: char) {
+        match character {
+            '7' => self.save_cursor(),
+            '8' => self.restore_cursor(),
+            _ => {}
+        }
+    }
+
+    fn control_sequence(&mut self, sequence: &ControlSequence) {
+        let row = self.row_position;
+        let column = self.column_position;
+
+        // Most sequences take a count (or a 1-based position) defaulting to 1.
+        let n = sequence.param(0, 1) as usize;
+
+        match (sequence.private, sequence.final_char) {
+            // Cursor up, down, forward and back (CUU, CUD, CUF, CUB).
+            (false, 'A') => self.move_to(row.saturating_sub(n), column),
+            (false, 'B') => self.move_to(row + n, column),
+            (false, 'C') => self.move_to(row, column + n),
+            (false, 'D') => self.move_to(row, column.saturating_sub(n)),
+            // Cursor to the start of the next or previous line (CNL, CPL).
+            (false, 'E') => self.move_to(row + n, 0),
+            (false, 'F') => self.move_to(row.saturating_sub(n), 0),
+            // Cursor to a column (CHA), row (VPA), or both (CUP, HVP).
+            (false, 'G') => self.move_to(row, n - 1),
+            (false, 'd') => self.move_to(n - 1, column),
+            (false, 'H') | (false, 'f') => {
+                self.move_to(n - 1, sequence.param(1, 1) as usize - 1);
+            }
+            // Erase in display (ED) and erase in line (EL).
+            (false, 'J') => self.erase_in_display(sequence.param(0, 0)),
+            (false, 'K') => self.erase_in_line(sequence.param(0, 0)),
+            // Select graphic rendition (SGR).
+            (false, 'm') => self.select_graphic_rendition(sequence.params()),
+            // Save and restore the cursor (SCP, RCP).
+            (false, 's') => self.save_cursor(),
+            (false, 'u') => self.restore_cursor(),
+            // Show and hide the cursor (DECTCEM).
+            (true, 'h') if sequence.param(0, 0) == 25 => self.cursor.show(),
+            (true, 'l') if sequence.param(0, 0) == 25 => self.cursor.hide(),
+            _ => {}
+        }
+    }
+
+    // 0 erases from the write position to the end of the screen, 1 from the
+    // start of the screen to the write position, and 2 (or 3) all of it.
+    fn erase_in_display(&mut self, mode: u16) {
+        let row = self.row_position;
+
+        match mode {
+            0 => {
+                self.erase_in_line(0);
+
+                for row in row + 1..BUFFER_HEIGHT {
+                    self.clear_row(row);
+                }
+            }
+            1 => {
+                for row in 0..row {
+                    self.clear_row(row);
+                }
+
+                self.erase_in_line(1);
+            }
+            2 | 3 => {
+                for row in 0..BUFFER_HEIGHT {
+                    self.clear_row(row);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Like `erase_in_display()`, but within the current row.
+    fn erase_in_line(&mut self, mode: u16) {
+        let column = self.column_position.min(BUFFER_WIDTH - 1);
+
+        match mode {
+            0 => self.clear(self.row_position, column..BUFFER_WIDTH),
+            1 => self.clear(self.row_position, 0..column + 1),
+            2 => self.clear_row(self.row_position),
+            _ => {}
+        }
+    }
+
+    fn select_graphic_rendition(&mut self, params: &[u16]) {
+        // `ESC [ m` is the same as `ESC [ 0 m`.
+        if params.is_empty() {
+            self.reset_attributes();
+        }
+
+        let mut params = params.iter().copied();
+
+        while let Some(param) = params.next() {
+            let code = self.color_code;
+
+            let bold = if self.bold { 8 } else { 0 };
+
+            match param {
+                0 => self.reset_attributes(),
+                1 => {
+                    self.bold = true;
+                    self.color_code = code.with_foreground(code.foreground() | 8);
+                }
+                22 => {
+                    if self.bold {
+                        self.color_code = code.with_foreground(code.foreground() & 7);
+                    }
+
+                    self.bold = false;
+                }
+                30..=37 => self.color_code = code.with_foreground(ansi_color(param - 30) | bold),
+                39 => {
+                    let foreground = self.default_color_code.foreground();
+
+                    self.color_code = code.with_foreground(foreground | bold);
+                }
+                40..=47 => self.color_code = code.with_background(ansi_color(param - 40)),
+                49 => {
+                    self.color_code = code.with_background(self.default_color_code.background());
+                }
+                90..=97 => self.color_code = code.with_foreground(ansi_color(param - 90 + 8)),
+                100..=107 => self.color_code = code.with_background(ansi_color(param - 100 + 8)),
+                // 256-color (`38;5;n`) and RGB (`38;2;r;g;b`) colors. Only the
+                // first 16 of the 256 colors have VGA equivalents.
+                38 | 48 => {
+                    let color = match params.next() {
+                        Some(5) => params.next().filter(|&number| number < 16),
+                        Some(2) => {
+                            params.nth(2);
+
+                            None
+                        }
+                        _ => None,
+                    };
+
+                    match (param, color) {
+                        (38, Some(number)) => {
+                            self.color_code = code.with_foreground(ansi_color(number));
+                        }
+                        (48, Some(number)) => {
+                            self.color_code = code.with_background(ansi_color(number));
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn reset_attributes(&mut self) {
+        self.color_code = self.default_color_code;
+        self.bold = false;
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = Some(SavedCursor {
+            row: self.row_position,
+            column: self.column_position,
+            color_code: self.color_code,
+            bold: self.bold,
+        });
+    }
+
+    fn restore_cursor(&mut self) {
+        if let Some(saved) = self.saved_cursor {
+            self.color_code = saved.color_code;
+            self.bold = saved.bold;
+
+            // The saved column may be just past the end of its row.
+            self.row_position = saved.row;
+            self.column_position = saved.column;
+        }
+    }
 }
 
+// `write!()` calls `write_str()` once per formatted piece, and each moves the
+// hardware cursor (through `write_string()`), so that it's in place however
+// the writer is used.
 impl fmt::Write for Writer {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         self.write_string(s);
@@ -130,9 +456,16 @@ impl fmt::Write for Writer {
 
 lazy_static! {
     pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
+        // Output starts on the bottom row, and scrolls up from there.
+        row_position: BUFFER_HEIGHT - 1,
         column_position: 0,
         color_code: ColorCode::new(Color::Yellow, Color::Black),
+        default_color_code: ColorCode::new(Color::Yellow, Color::Black),
+        bold: false,
+        saved_cursor: None,
+        parser: Parser::new(),
         buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
+        cursor: unsafe { Cursor::new() },
     });
 }
 
@@ -147,6 +480,15 @@ macro_rules! println {
     ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
 }
 
+// Like `println!`, but prefixed with the current date and time (from the RTC),
+// for log messages. Reading the RTC takes a while, so this isn't meant for
+// interrupt handlers.
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => ($crate::println!(
+        "[{}] {}", $crate::time::rtc::now(), format_args!($($arg)*)));
+}
+
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     use core::fmt::Write;
@@ -190,3 +532,82 @@ fn test_println_output() {
         }
     });
 }
+
+#[test_case]
+fn test_cursor_follows_writes() {
+    use core::fmt::Write;
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+
+        writer.write_string("\nab");
+
+        let position = writer.cursor().position();
+
+        assert_eq!(position, (BUFFER_HEIGHT - 1) * BUFFER_WIDTH + 2);
+
+        write!(writer, "cd").expect("write failed");
+
+        assert_eq!(writer.cursor().position(), position + 2);
+
+        writer.cursor().hide();
+
+        assert!(!writer.cursor().is_visible());
+
+        writer.cursor().show();
+
+        assert!(writer.cursor().is_visible());
+    });
+}
+
+#[test_case]
+fn test_ansi_colors_and_cursor_movement() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+
+        // Draws in the top-left corner, then returns to where we were.
+        writer.write_string("\u{1b}7\u{1b}[1;1H\u{1b}[1;31;44mA\u{1b}[0mB");
+
+        let a = writer.buffer.chars[0][0].read();
+        let b = writer.buffer.chars[0][1].read();
+
+        assert_eq!(a.color_code, ColorCode::new(Color::LightRed, Color::Blue));
+        assert_eq!((b.ascii_character, b.color_code), (b'B', writer.default_color_code));
+
+        writer.write_string("\u{1b}[2D\u{1b}[93mC");
+
+        let c = writer.buffer.chars[0][0].read();
+
+        assert_eq!(c.ascii_character, b'C');
+        assert_eq!(c.color_code, ColorCode::new(Color::Yellow, Color::Black));
+
+        writer.write_string("\u{1b}[2K\u{1b}8");
+
+        assert_eq!(writer.buffer.chars[0][1].read().ascii_character, b' ');
+        assert_eq!(writer.row_position, BUFFER_HEIGHT - 1);
+        assert_eq!(writer.color_code, writer.default_color_code);
+    });
+}
+
+#[test_case]
+fn test_glyphs_are_not_control_characters() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        let mut writer = WRITER.lock();
+
+        writer.write_string("\n");
+
+        let row = writer.row_position;
+
+        // '◙' is 0x0a in CP437, the same byte as '\n'.
+        writer.write_string("◙");
+
+        assert_eq!(writer.row_position, row);
+        assert_eq!(writer.column_position(), 1);
+        assert_eq!(writer.buffer.chars[row][0].read().ascii_character, 0x0a);
+    });
+}
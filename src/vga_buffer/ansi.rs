@@ -0,0 +1,184 @@
+// A parser for the subset of ANSI (VT100) escape sequences that the VGA writer
+// understands: two-character escapes (ESC 7, ESC 8, ...), and control sequences
+// (ESC [ followed by numeric parameters and a final byte). Anything else is
+// passed through to be printed.
+
+const ESCAPE: char = '\u{1b}';
+
+// Cancel and substitute abort a sequence in progress.
+const CANCEL: char = '\u{18}';
+const SUBSTITUTE: char = '\u{1a}';
+
+// More parameters than this are ignored.
+const MAX_PARAMS: usize = 16;
+
+// A parsed control sequence, e.g. `ESC [ 1 ; 31 m`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ControlSequence {
+    params: [u16; MAX_PARAMS],
+    len: usize,
+    // Set by a leading `?`, as in DEC private modes (e.g. `ESC [ ? 25 l`).
+    pub(super) private: bool,
+    pub(super) final_char: char,
+}
+
+impl ControlSequence {
+    pub(super) fn params(&self) -> &[u16] {
+        &self.params[..self.len]
+    }
+
+    // The parameter at `index`, or `default` if it was omitted (or is 0,
+    // which means the same for every sequence that has a non-zero default).
+    pub(super) fn param(&self, index: usize, default: u16) -> u16 {
+        match self.params().get(index) {
+            Some(&param) if param != 0 => param,
+            _ => default,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Action {
+    // An ordinary character (including control characters like '\n').
+    Print(char),
+    // The character following an ESC (other than '[').
+    Escape(char),
+    Control(ControlSequence),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    ControlSequence,
+}
+
+pub(super) struct Parser {
+    state: State,
+    sequence: ControlSequence,
+    // Index of the parameter being parsed.
+    param_index: usize,
+}
+
+impl Parser {
+    pub(super) const fn new() -> Self {
+        Parser {
+            state: State::Ground,
+            sequence: ControlSequence {
+                params: [0; MAX_PARAMS],
+                len: 0,
+                private: false,
+                final_char: '\0',
+            },
+            param_index: 0,
+        }
+    }
+
+    // Feeds one character to the parser, returning what (if anything) should
+    // be done about it.
+    pub(super) fn advance(&mut self, character: char) -> Option<Action> {
+        if character == CANCEL || character == SUBSTITUTE {
+            self.state = State::Ground;
+
+            return None;
+        }
+
+        match self.state {
+            State::Ground if character == ESCAPE => {
+                self.state = State::Escape;
+
+                None
+            }
+            State::Ground => Some(Action::Print(character)),
+            State::Escape if character == '[' => {
+                self.state = State::ControlSequence;
+
+                self.sequence.params = [0; MAX_PARAMS];
+                self.sequence.len = 0;
+                self.sequence.private = false;
+                self.param_index = 0;
+
+                None
+            }
+            State::Escape => {
+                self.state = State::Ground;
+
+                Some(Action::Escape(character))
+            }
+            State::ControlSequence => self.advance_control_sequence(character),
+        }
+    }
+
+    fn advance_control_sequence(&mut self, character: char) -> Option<Action> {
+        let sequence = &mut self.sequence;
+
+        match character {
+            '0'..='9' => {
+                if let Some(param) = sequence.params.get_mut(self.param_index) {
+                    let digit = character as u16 - '0' as u16;
+
+                    *param = param.saturating_mul(10).saturating_add(digit);
+
+                    sequence.len = self.param_index + 1;
+                }
+
+                None
+            }
+            // Empty parameters (e.g. the first in `ESC [ ; 5 H`) are left as 0.
+            ';' => {
+                self.param_index += 1;
+
+                None
+            }
+            '?' => {
+                sequence.private = true;
+
+                None
+            }
+            // A final byte ends the sequence.
+            '\u{40}'..='\u{7e}' => {
+                self.state = State::Ground;
+
+                sequence.final_char = character;
+
+                Some(Action::Control(*sequence))
+            }
+            // Intermediate bytes (and anything unexpected) are ignored.
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+fn parse(s: &str) -> Option<Action> {
+    let mut parser = Parser::new();
+
+    s.chars().filter_map(|character| parser.advance(character)).last()
+}
+
+#[test_case]
+fn test_parse_control_sequences() {
+    match parse("\u{1b}[1;31m") {
+        Some(Action::Control(sequence)) => {
+            assert_eq!(sequence.params(), [1, 31]);
+            assert_eq!(sequence.final_char, 'm');
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    match parse("\u{1b}[;5H") {
+        Some(Action::Control(sequence)) => {
+            assert_eq!(sequence.param(0, 1), 1);
+            assert_eq!(sequence.param(1, 1), 5);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    match parse("\u{1b}[?25l") {
+        Some(Action::Control(sequence)) => assert!(sequence.private),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    assert_eq!(parse("\u{1b}7"), Some(Action::Escape('7')));
+    assert_eq!(parse("\u{1b}[1\u{18}x"), Some(Action::Print('x')));
+}
@@ -0,0 +1,72 @@
+// Code page 437, the VGA's built-in character set: ASCII, plus glyphs for
+// accented Latin letters, Greek letters, box drawing, block elements, and a
+// few symbols.
+
+// Shown for characters that have no CP437 glyph.
+pub const REPLACEMENT: u8 = 0xfe;
+
+// The glyphs at 0x01-0x1f. Written to the screen directly, these show as
+// symbols rather than acting as control characters.
+const LOW_GLYPHS: [char; 31] = [
+    '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼', //
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+];
+
+// The glyphs at 0x80-0xff.
+const HIGH_GLYPHS: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+// The CP437 byte for `character`, if it has a glyph.
+pub fn lookup(character: char) -> Option<u8> {
+    match character {
+        ' '..='~' => Some(character as u8),
+        '⌂' => Some(0x7f),
+        // Characters that look the same as (or are commonly drawn with) one of
+        // CP437's glyphs.
+        'β' => Some(0xe1),
+        '∑' => Some(0xe4),
+        'μ' => Some(0xe6),
+        // The ohm sign.
+        '\u{2126}' => Some(0xea),
+        'ϕ' | '∅' => Some(0xed),
+        'ϵ' | '∈' => Some(0xee),
+        _ => {
+            if let Some(index) = HIGH_GLYPHS.iter().position(|&glyph| glyph == character) {
+                Some(0x80 + index as u8)
+            } else {
+                LOW_GLYPHS
+                    .iter()
+                    .position(|&glyph| glyph == character)
+                    .map(|index| 0x01 + index as u8)
+            }
+        }
+    }
+}
+
+// The CP437 byte for `character`, or REPLACEMENT if it has no glyph.
+pub fn encode(character: char) -> u8 {
+    lookup(character).unwrap_or(REPLACEMENT)
+}
+
+#[test_case]
+fn test_cp437_lookup() {
+    assert_eq!(encode('A'), b'A');
+    assert_eq!(encode('é'), 0x82);
+    assert_eq!(encode('ß'), 0xe1);
+    assert_eq!(encode('─'), 0xc4);
+    assert_eq!(encode('╬'), 0xce);
+    assert_eq!(encode('▀'), 0xdf);
+    assert_eq!(encode('Σ'), 0xe4);
+    assert_eq!(encode('♥'), 0x03);
+
+    assert_eq!(lookup('€'), None);
+    assert_eq!(encode('€'), REPLACEMENT);
+}
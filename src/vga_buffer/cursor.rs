@@ -0,0 +1,94 @@
+use x86_64::instructions::port::Port;
+
+// The CRT controller's registers are reached through an index port (selecting
+// a register) and a data port (reading or writing it).
+const CRTC_INDEX_PORT: u16 = 0x3d4;
+const CRTC_DATA_PORT: u16 = 0x3d5;
+
+// CRTC registers.
+const CURSOR_START: u8 = 0x0a;
+const CURSOR_END: u8 = 0x0b;
+const CURSOR_LOCATION_HIGH: u8 = 0x0e;
+const CURSOR_LOCATION_LOW: u8 = 0x0f;
+
+// Set in the cursor start register to hide the cursor.
+const CURSOR_DISABLE: u8 = 1 << 5;
+
+// Cursor start/end scanlines are 5 bits wide.
+const SCANLINE_MASK: u8 = 0x1f;
+
+// The hardware (blinking) text-mode cursor. Owned by the `Writer`, so access
+// to the CRTC is serialized by the WRITER lock.
+pub struct Cursor {
+    index: Port<u8>,
+    data: Port<u8>,
+}
+
+impl Cursor {
+    // Unsafe, since only one `Cursor` may exist at a time.
+    pub(super) unsafe fn new() -> Self {
+        Cursor {
+            index: Port::new(CRTC_INDEX_PORT),
+            data: Port::new(CRTC_DATA_PORT),
+        }
+    }
+
+    fn read(&mut self, register: u8) -> u8 {
+        unsafe {
+            self.index.write(register);
+
+            self.data.read()
+        }
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        unsafe {
+            self.index.write(register);
+
+            self.data.write(value);
+        }
+    }
+
+    // Moves the cursor to the given cell, counted from the top-left corner,
+    // row by row.
+    pub fn set_position(&mut self, position: usize) {
+        let position = position as u16;
+
+        self.write(CURSOR_LOCATION_LOW, position as u8);
+        self.write(CURSOR_LOCATION_HIGH, (position >> 8) as u8);
+    }
+
+    pub fn position(&mut self) -> usize {
+        let low = self.read(CURSOR_LOCATION_LOW) as usize;
+        let high = self.read(CURSOR_LOCATION_HIGH) as usize;
+
+        high << 8 | low
+    }
+
+    pub fn show(&mut self) {
+        let start = self.read(CURSOR_START);
+
+        self.write(CURSOR_START, start & !CURSOR_DISABLE);
+    }
+
+    pub fn hide(&mut self) {
+        let start = self.read(CURSOR_START);
+
+        self.write(CURSOR_START, start | CURSOR_DISABLE);
+    }
+
+    pub fn is_visible(&mut self) -> bool {
+        self.read(CURSOR_START) & CURSOR_DISABLE == 0
+    }
+
+    // Sets which scanlines of its cell the cursor covers, from 0 (the top) to
+    // 15 (the bottom, with the standard 16-line font): e.g. 14-15 for an
+    // underline, or 0-15 for a block. Preserves the cursor's visibility.
+    pub fn set_shape(&mut self, start: u8, end: u8) {
+        let start_register = self.read(CURSOR_START);
+        let end_register = self.read(CURSOR_END);
+
+        self.write(CURSOR_START, (start_register & !SCANLINE_MASK) | (start & SCANLINE_MASK));
+        self.write(CURSOR_END, (end_register & !SCANLINE_MASK) | (end & SCANLINE_MASK));
+    }
+}
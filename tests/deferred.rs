@@ -0,0 +1,95 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(rust_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+
+use core::{
+    future::Future,
+    panic::PanicInfo,
+    sync::atomic::{AtomicU64, Ordering},
+    task::Context,
+};
+
+use bootloader::{entry_point, BootInfo};
+
+use futures_util::task::noop_waker_ref;
+
+use rust_os::interrupts::deferred::{self, Work};
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    use rust_os::allocator;
+    use rust_os::memory::{self, BootInfoFrameAllocator};
+    use x86_64::VirtAddr;
+
+    rust_os::init();
+
+    let physical_offset = VirtAddr::new(boot_info.physical_memory_offset);
+
+    let mut mapper = unsafe { memory::init(physical_offset) };
+
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("Heap initialization failed.");
+
+    deferred::init();
+
+    test_main();
+
+    loop {}
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    rust_os::test_panic_handler(info)
+}
+
+static TOTAL: AtomicU64 = AtomicU64::new(0);
+
+fn add_to_total(amount: u64) {
+    TOTAL.fetch_add(amount, Ordering::Relaxed);
+}
+
+#[test_case]
+fn deferred_task_runs_scheduled_work() {
+    let mut task = Box::pin(deferred::process_deferred_work_task());
+
+    let mut context = Context::from_waker(noop_waker_ref());
+
+    let before = TOTAL.load(Ordering::Relaxed);
+
+    assert!(deferred::schedule(Work::new(add_to_total, 3)));
+    assert!(deferred::schedule(Work::new(add_to_total, 4)));
+
+    // The task never finishes; it runs the work and then waits for more.
+    assert!(task.as_mut().poll(&mut context).is_pending());
+
+    assert_eq!(TOTAL.load(Ordering::Relaxed) - before, 7);
+}
+
+#[test_case]
+fn full_queue_drops_work() {
+    let before = TOTAL.load(Ordering::Relaxed);
+    let dropped = deferred::dropped_count();
+
+    // Nothing drains the queue here, so it eventually fills up.
+    let mut scheduled = 0;
+
+    while deferred::schedule(Work::new(add_to_total, 1)) {
+        scheduled += 1;
+
+        assert!(scheduled <= 1024, "the deferred work queue never filled up");
+    }
+
+    assert_eq!(deferred::dropped_count() - dropped, 1);
+
+    // Everything that was queued still runs.
+    assert_eq!(deferred::run_pending() as u64, scheduled);
+    assert_eq!(TOTAL.load(Ordering::Relaxed) - before, scheduled);
+}
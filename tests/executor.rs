@@ -0,0 +1,594 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(rust_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use core::{
+    future::Future,
+    panic::PanicInfo,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    task::{Context, Poll},
+};
+
+use bootloader::{entry_point, BootInfo};
+
+extern crate alloc;
+
+use alloc::{boxed::Box, sync::Arc};
+
+use rust_os::task::{
+    channel::{broadcast, mpsc, oneshot},
+    combinator::join_all,
+    executor::{watchdog, Executor, TaskState},
+    join::JoinError,
+    scope::scope,
+    sync::{Mutex, Notify, RwLock, Semaphore},
+    Priority, Task,
+};
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    use rust_os::allocator;
+    use rust_os::memory::{self, BootInfoFrameAllocator};
+    use x86_64::VirtAddr;
+
+    rust_os::init();
+
+    let physical_offset = VirtAddr::new(boot_info.physical_memory_offset);
+
+    let mut mapper = unsafe { memory::init(physical_offset) };
+
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("Heap initialization failed.");
+
+    test_main();
+
+    loop {}
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    rust_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn join_handle_returns_output() {
+    let mut executor = Executor::new();
+
+    let spawner = executor.spawner();
+
+    let outer = executor.spawn_with_handle(async move {
+        let inner = spawner.spawn_with_handle(async { 6 * 7 });
+
+        assert_eq!(inner.await, Ok(42));
+    });
+
+    executor.run_until_idle();
+
+    assert!(outer.is_finished());
+}
+
+#[test_case]
+fn aborted_task_is_cancelled() {
+    let mut executor = Executor::new();
+
+    // Never resolves, so it can only finish by being aborted.
+    let pending = executor.spawn_with_handle(core::future::pending::<()>());
+
+    executor.run_until_idle();
+
+    assert!(!pending.is_finished());
+
+    pending.abort();
+
+    let result = executor.spawn_with_handle(pending);
+
+    executor.run_until_idle();
+
+    assert!(result.is_finished());
+
+    let checked = executor.spawn_with_handle(async move {
+        assert_eq!(result.await.unwrap(), Err(JoinError::Cancelled));
+    });
+
+    executor.run_until_idle();
+
+    assert!(checked.is_finished());
+}
+
+// Yields once, waking its own task twice before doing so.
+struct YieldTwiceWoken {
+    yielded: bool,
+}
+
+impl Future for YieldTwiceWoken {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+
+        self.yielded = true;
+
+        cx.waker().wake_by_ref();
+        cx.waker().wake_by_ref();
+
+        Poll::Pending
+    }
+}
+
+#[test_case]
+fn many_tasks_with_duplicate_wakeups() {
+    const TASK_COUNT: u64 = 2_000;
+
+    static COMPLETED: AtomicU64 = AtomicU64::new(0);
+
+    let mut executor = Executor::new();
+
+    for _ in 0..TASK_COUNT {
+        executor.spawn(Task::new(async {
+            YieldTwiceWoken { yielded: false }.await;
+
+            COMPLETED.fetch_add(1, Ordering::Relaxed);
+        }));
+    }
+
+    executor.run_until_idle();
+
+    assert_eq!(COMPLETED.load(Ordering::Relaxed), TASK_COUNT);
+}
+
+// Stays ready (re-waking itself on every poll) until `DONE` is set.
+struct BusyUntilDone;
+
+static DONE: AtomicBool = AtomicBool::new(false);
+
+impl Future for BusyUntilDone {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if DONE.load(Ordering::Relaxed) {
+            return Poll::Ready(());
+        }
+
+        cx.waker().wake_by_ref();
+
+        Poll::Pending
+    }
+}
+
+#[test_case]
+fn background_task_is_not_starved() {
+    let mut executor = Executor::new();
+
+    for priority in [Priority::InterruptDeferred, Priority::Interactive, Priority::Normal] {
+        executor.spawn(Task::with_priority(BusyUntilDone, priority));
+    }
+
+    executor.spawn(Task::with_priority(
+        async { DONE.store(true, Ordering::Relaxed) },
+        Priority::Background,
+    ));
+
+    // Only returns once the busy tasks observe `DONE`.
+    executor.run_until_idle();
+
+    assert!(DONE.load(Ordering::Relaxed));
+}
+
+#[test_case]
+fn mutex_is_held_across_await() {
+    let mut executor = Executor::new();
+
+    let counter = Arc::new(Mutex::new(0));
+    let notify = Arc::new(Notify::new());
+
+    let (first_counter, first_notify) = (counter.clone(), notify.clone());
+
+    // Holds the lock until the second task has been notified, below.
+    let first = executor.spawn_with_handle(async move {
+        let mut guard = first_counter.lock().await;
+
+        first_notify.notified().await;
+
+        *guard += 1;
+    });
+
+    let second_counter = counter.clone();
+
+    let second = executor.spawn_with_handle(async move {
+        *second_counter.lock().await += 1;
+    });
+
+    executor.run_until_idle();
+
+    assert!(!first.is_finished());
+    assert!(!second.is_finished());
+
+    notify.notify_one();
+
+    executor.run_until_idle();
+
+    assert!(first.is_finished());
+    assert!(second.is_finished());
+    assert_eq!(*counter.try_lock().unwrap(), 2);
+}
+
+#[test_case]
+fn rwlock_readers_exclude_writers() {
+    let mut executor = Executor::new();
+
+    let lock = Arc::new(RwLock::new(0));
+    let notify = Arc::new(Notify::new());
+
+    let (reader_lock, reader_notify) = (lock.clone(), notify.clone());
+
+    // Holds a read lock until it's notified, below.
+    let reader = executor.spawn_with_handle(async move {
+        let guard = reader_lock.read().await;
+
+        reader_notify.notified().await;
+
+        assert_eq!(*guard, 0);
+    });
+
+    let writer_lock = lock.clone();
+
+    let writer = executor.spawn_with_handle(async move {
+        *writer_lock.write().await += 1;
+    });
+
+    executor.run_until_idle();
+
+    assert!(!reader.is_finished());
+    assert!(!writer.is_finished());
+
+    // The queued writer holds back new readers, as well as other writers.
+    assert!(lock.try_read().is_none());
+    assert!(lock.try_write().is_none());
+
+    notify.notify_one();
+
+    executor.run_until_idle();
+
+    assert!(reader.is_finished());
+    assert!(writer.is_finished());
+
+    // Any number of readers may hold the lock at once, but no writer.
+    let first = lock.try_read().unwrap();
+    let second = lock.try_read().unwrap();
+
+    assert_eq!((*first, *second), (1, 1));
+    assert!(lock.try_write().is_none());
+}
+
+#[test_case]
+fn semaphore_limits_concurrent_permits() {
+    static ACQUIRED: AtomicU64 = AtomicU64::new(0);
+
+    let mut executor = Executor::new();
+
+    let semaphore = Arc::new(Semaphore::new(2));
+    let notify = Arc::new(Notify::new());
+
+    // Each task holds its permit until it's notified.
+    for _ in 0..3 {
+        let (semaphore, notify) = (semaphore.clone(), notify.clone());
+
+        executor.spawn(Task::new(async move {
+            let _permit = semaphore.acquire().await;
+
+            ACQUIRED.fetch_add(1, Ordering::Relaxed);
+
+            notify.notified().await;
+        }));
+    }
+
+    executor.run_until_idle();
+
+    assert_eq!(ACQUIRED.load(Ordering::Relaxed), 2);
+    assert_eq!(semaphore.available_permits(), 0);
+    assert!(semaphore.try_acquire().is_none());
+
+    // Releases the first two permits, one of which goes to the third task.
+    notify.notify_waiters();
+
+    executor.run_until_idle();
+
+    assert_eq!(ACQUIRED.load(Ordering::Relaxed), 3);
+    assert_eq!(semaphore.available_permits(), 1);
+
+    notify.notify_waiters();
+
+    executor.run_until_idle();
+
+    assert_eq!(semaphore.available_permits(), 2);
+
+    let permits = semaphore.try_acquire_many(2).unwrap();
+
+    assert_eq!(semaphore.available_permits(), 0);
+
+    drop(permits);
+
+    assert_eq!(semaphore.available_permits(), 2);
+}
+
+#[test_case]
+fn bounded_channel_applies_backpressure() {
+    let mut executor = Executor::new();
+
+    let (sender, mut receiver) = mpsc::channel::<u64>(2);
+    let (reply_sender, reply_receiver) = oneshot::channel();
+
+    // Sends more values than the channel can hold at once.
+    executor.spawn(Task::new(async move {
+        for value in 0..10 {
+            sender.send(value).await.unwrap();
+        }
+    }));
+
+    executor.spawn(Task::new(async move {
+        let mut sum = 0;
+
+        while let Some(value) = receiver.recv().await {
+            sum += value;
+        }
+
+        reply_sender.send(sum).unwrap();
+    }));
+
+    let sum = executor.spawn_with_handle(reply_receiver);
+
+    executor.run_until_idle();
+
+    assert!(sum.is_finished());
+
+    executor.spawn(Task::new(async move {
+        assert_eq!(sum.await, Ok(Ok(45)));
+    }));
+
+    executor.run_until_idle();
+}
+
+#[test_case]
+fn unbounded_channel_never_blocks_senders() {
+    let mut executor = Executor::new();
+
+    let (sender, mut receiver) = mpsc::unbounded_channel::<u64>();
+
+    // Far more values than are ever received at once.
+    for value in 0..1_000 {
+        sender.send(value).unwrap();
+    }
+
+    let second_sender = sender.clone();
+
+    executor.spawn(Task::new(async move {
+        second_sender.send(1_000).unwrap();
+    }));
+
+    drop(sender);
+
+    // Only finishes once both senders have been dropped.
+    let checked = executor.spawn_with_handle(async move {
+        let mut sum = 0;
+
+        while let Some(value) = receiver.recv().await {
+            sum += value;
+        }
+
+        assert_eq!(sum, 1_000 * 1_001 / 2);
+    });
+
+    executor.run_until_idle();
+
+    assert!(checked.is_finished());
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    receiver.close();
+
+    assert!(sender.is_closed());
+    assert_eq!(sender.send(1), Err(mpsc::SendError(1)));
+}
+
+#[test_case]
+fn broadcast_reaches_every_receiver() {
+    let mut executor = Executor::new();
+
+    let (sender, first) = broadcast::channel::<u64>(4);
+    let second = sender.subscribe();
+
+    let handles = [first, second].map(|mut receiver| {
+        executor.spawn_with_handle(async move {
+            let mut sum = 0;
+
+            loop {
+                match receiver.recv().await {
+                    Ok(value) => sum += value,
+                    Err(error) => return (sum, error),
+                }
+            }
+        })
+    });
+
+    // Both receivers wait for values, without seeing any.
+    executor.run_until_idle();
+
+    for value in 1..=3 {
+        sender.send(value);
+
+        executor.run_until_idle();
+    }
+
+    assert!(handles.iter().all(|handle| !handle.is_finished()));
+
+    drop(sender);
+
+    let checked = executor.spawn_with_handle(async move {
+        for handle in handles {
+            assert_eq!(handle.await, Ok((6, broadcast::RecvError::Closed)));
+        }
+    });
+
+    executor.run_until_idle();
+
+    assert!(checked.is_finished());
+}
+
+#[test_case]
+fn lagging_broadcast_receiver_skips_ahead() {
+    let mut executor = Executor::new();
+
+    let (sender, mut receiver) = broadcast::channel(2);
+
+    // Overwrites the first three values before the receiver sees them.
+    for value in 0..5 {
+        sender.send(value);
+    }
+
+    drop(sender);
+
+    let checked = executor.spawn_with_handle(async move {
+        assert_eq!(receiver.recv().await, Err(broadcast::RecvError::Lagged(3)));
+        assert_eq!(receiver.recv().await, Ok(3));
+        assert_eq!(receiver.recv().await, Ok(4));
+        assert_eq!(receiver.recv().await, Err(broadcast::RecvError::Closed));
+    });
+
+    executor.run_until_idle();
+
+    assert!(checked.is_finished());
+}
+
+#[test_case]
+fn snapshot_lists_waiting_tasks() {
+    let mut executor = Executor::new();
+
+    executor.spawn(Task::new(YieldTwiceWoken { yielded: false }).named("yielder"));
+    executor.spawn(Task::new(core::future::pending::<()>()).named("sleeper"));
+
+    executor.run_until_idle();
+
+    let snapshot = executor.snapshot();
+
+    // The yielder has finished, and so is no longer listed.
+    assert_eq!(snapshot.tasks().len(), 1);
+
+    let sleeper = &snapshot.tasks()[0];
+
+    assert_eq!(sleeper.name, "sleeper");
+    assert_eq!(sleeper.state, TaskState::Waiting);
+    assert_eq!(sleeper.poll_count, 1);
+    assert!(sleeper.last_woken.is_none());
+}
+
+// Busy-waits (without yielding) for the given number of timer ticks.
+async fn spin_for_ticks(ticks: u64) {
+    let deadline = rust_os::time::ticks() + ticks;
+
+    while rust_os::time::ticks() < deadline {}
+}
+
+#[test_case]
+fn watchdog_reports_stuck_poll() {
+    let slow_before = watchdog::slow_poll_count();
+    let stuck_before = watchdog::stuck_poll_count();
+
+    watchdog::set_watchdog_ticks(2);
+
+    let mut executor = Executor::new();
+
+    executor.spawn(Task::new(spin_for_ticks(5)));
+
+    executor.run_until_idle();
+
+    watchdog::set_watchdog_ticks(rust_os::time::TICKS_PER_SECOND);
+
+    assert_eq!(watchdog::slow_poll_count(), slow_before + 1);
+    assert_eq!(watchdog::stuck_poll_count(), stuck_before + 1);
+}
+
+#[test_case]
+fn join_and_select_combinators() {
+    let mut executor = Executor::new();
+
+    let handle = executor.spawn_with_handle(async {
+        let (a, b) = rust_os::join!(async { 1 }, async {
+            YieldTwiceWoken { yielded: false }.await;
+
+            2
+        });
+
+        let all = join_all((0..3).map(|value| async move { value * 2 })).await;
+
+        let selected = rust_os::select! {
+            _ = core::future::pending::<()>() => 0,
+            value = async { 5 } => value,
+        };
+
+        (a, b, all, selected)
+    });
+
+    let checked = executor.spawn_with_handle(async move {
+        let (a, b, all, selected) = handle.await.unwrap();
+
+        assert_eq!((a, b), (1, 2));
+        assert_eq!(all, [0, 2, 4]);
+        assert_eq!(selected, 5);
+    });
+
+    executor.run_until_idle();
+
+    assert!(checked.is_finished());
+}
+
+#[test_case]
+fn scope_outlives_its_children() {
+    static FINISHED_CHILDREN: AtomicU64 = AtomicU64::new(0);
+
+    let mut executor = Executor::new();
+
+    let spawner = executor.spawner();
+
+    let waited = executor.spawn_with_handle(scope(spawner.clone(), |scope| {
+        Box::pin(async move {
+            for _ in 0..3 {
+                // Never awaited by the body; the scope waits for them anyway.
+                scope.spawn(async {
+                    YieldTwiceWoken { yielded: false }.await;
+
+                    FINISHED_CHILDREN.fetch_add(1, Ordering::Relaxed);
+                });
+            }
+        })
+    }));
+
+    executor.run_until_idle();
+
+    assert!(waited.is_finished());
+    assert_eq!(FINISHED_CHILDREN.load(Ordering::Relaxed), 3);
+
+    let cancelled = executor.spawn_with_handle(scope(spawner, |scope| {
+        Box::pin(async move {
+            let stuck = scope.spawn(core::future::pending::<()>());
+
+            scope.cancel();
+
+            stuck.await
+        })
+    }));
+
+    let checked = executor.spawn_with_handle(async move {
+        assert_eq!(cancelled.await, Ok(Err(JoinError::Cancelled)));
+    });
+
+    executor.run_until_idle();
+
+    assert!(checked.is_finished());
+}
@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(rust_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use core::panic::PanicInfo;
+
+use bootloader::{entry_point, BootInfo};
+
+use rust_os::time::{self, hpet, TickSource};
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    use rust_os::memory::{self, BootInfoFrameAllocator};
+    use x86_64::VirtAddr;
+
+    rust_os::init();
+
+    let physical_offset = VirtAddr::new(boot_info.physical_memory_offset);
+
+    let mut mapper = unsafe { memory::init(physical_offset) };
+
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+
+    hpet::init(&mut mapper, &mut frame_allocator).expect("HPET initialization failed.");
+
+    test_main();
+
+    loop {}
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    rust_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn main_counter_advances() {
+    let before = hpet::counter().unwrap();
+
+    for _ in 0..10_000 {
+        core::hint::spin_loop();
+    }
+
+    assert!(hpet::counter().unwrap() > before);
+    assert!(hpet::frequency_hz().unwrap() > 0);
+}
+
+#[test_case]
+fn drives_timer_ticks() {
+    time::set_tick_source(TickSource::Hpet).unwrap();
+
+    let start = time::ticks();
+
+    while time::ticks() < start + 5 {
+        x86_64::instructions::hlt();
+    }
+
+    assert_eq!(time::tick_source(), TickSource::Hpet);
+
+    time::set_tick_source(TickSource::Pit).unwrap();
+}
@@ -0,0 +1,101 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(rust_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use core::{
+    panic::PanicInfo,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use bootloader::{entry_point, BootInfo};
+
+use rust_os::{thread, time};
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    use rust_os::allocator;
+    use rust_os::memory::{self, BootInfoFrameAllocator};
+    use x86_64::VirtAddr;
+
+    rust_os::init();
+
+    let physical_offset = VirtAddr::new(boot_info.physical_memory_offset);
+
+    let mut mapper = unsafe { memory::init(physical_offset) };
+
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("Heap initialization failed.");
+
+    thread::init();
+
+    test_main();
+
+    loop {}
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    rust_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn join_returns_output() {
+    let handle = thread::spawn("answer", || 6 * 7);
+
+    assert_eq!(handle.join(), 42);
+}
+
+#[test_case]
+fn busy_threads_are_preempted() {
+    static STOP: AtomicBool = AtomicBool::new(false);
+    static COUNTERS: [AtomicU64; 2] = [AtomicU64::new(0), AtomicU64::new(0)];
+
+    // Neither thread ever yields; both only make progress if the timer
+    // preempts them.
+    let handles = [0, 1].map(|index| {
+        thread::spawn("spinner", move || {
+            while !STOP.load(Ordering::Relaxed) {
+                COUNTERS[index].fetch_add(1, Ordering::Relaxed);
+            }
+        })
+    });
+
+    // The boot thread spins too, rather than sleeping.
+    let deadline = time::ticks() + 20;
+
+    while time::ticks() < deadline {}
+
+    STOP.store(true, Ordering::Relaxed);
+
+    for handle in handles {
+        handle.join();
+    }
+
+    assert!(COUNTERS[0].load(Ordering::Relaxed) > 0);
+    assert!(COUNTERS[1].load(Ordering::Relaxed) > 0);
+}
+
+#[test_case]
+fn sleep_waits_for_the_duration() {
+    let start = time::ticks();
+
+    thread::sleep(Duration::from_millis(50));
+
+    assert!(time::ticks() - start >= 5);
+}
+
+#[test_case]
+fn detached_thread_runs_to_completion() {
+    static DONE: AtomicBool = AtomicBool::new(false);
+
+    drop(thread::spawn("detached", || DONE.store(true, Ordering::Release)));
+
+    while !DONE.load(Ordering::Acquire) {
+        thread::yield_now();
+    }
+}